@@ -1,10 +1,53 @@
+use nannou::prelude::*;
+
+use crate::Direction;
+
+/// Cells in rows and columns, a row at a time from the top left.
 pub struct Grid {
-    tiles: Vec<&Tile>,
+    pub columns: usize,
+    pub rows: usize,
+    pub tiles: Vec<Tile>,
 }
 
 impl Grid {
-    pub fn new() -> Self {
-        Self { tiles: Vec::new() }
+    /// Tiles of `tile_size`, their positions from the top left of the grid, up as in nannou.
+    pub fn new(columns: usize, rows: usize, tile_size: f32) -> Self {
+        let tiles = (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| Tile {
+                    position: pt2(column as f32 + 0.5, -(row as f32) - 0.5) * tile_size,
+                })
+            })
+            .collect();
+        Self {
+            columns,
+            rows,
+            tiles,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn index(&self, column: usize, row: usize) -> usize {
+        row * self.columns + column
+    }
+
+    /// The cell next to `index` in `direction`, None at the edge of the grid.
+    pub fn neighbour(&self, index: usize, direction: Direction) -> Option<usize> {
+        let (column, row) = (index % self.columns, index / self.columns);
+        match direction {
+            Direction::Right if column + 1 < self.columns => Some(index + 1),
+            Direction::Up if row > 0 => Some(index - self.columns),
+            Direction::Left if column > 0 => Some(index - 1),
+            Direction::Down if row + 1 < self.rows => Some(index + self.columns),
+            _ => None,
+        }
     }
 }
 
@@ -1,9 +1,15 @@
 use chrono;
 use nannou::rand::random_range;
 use nannou::{App, Draw};
+use std::cell::Cell;
 
 pub mod schemes;
 pub mod grid;
+pub mod picture;
+pub mod print;
+pub mod shapes;
+pub mod tween;
+pub mod wfc;
 
 /// Things that can be drawn on the screen.
 pub trait Nannou {
@@ -29,6 +35,9 @@ pub struct Record {
     pub tmp_location: String,
     pub video_location: String,
     pub started_at: chrono::DateTime<chrono::Local>,
+    /// Frames written so far. The files are numbered by it, not by the frames of the app,
+    /// so that ffmpeg finds them all when some frames are skipped.
+    pub frames: Cell<u64>,
 }
 impl Record {
     pub fn new(app: &App) -> Self {
@@ -48,6 +57,7 @@ impl Record {
             tmp_location: location.to_string_lossy().to_string(),
             video_location: format!("{}/{}{}", saves_location(), unique_name, ".mp4"),
             started_at: now,
+            frames: Cell::new(0),
         }
     }
 
@@ -60,13 +70,9 @@ impl Record {
             return;
         }
 
-        let file_name = format!(
-            "{}/{}{}.png",
-            self.tmp_location,
-            "frame",
-            app.elapsed_frames(),
-        );
+        let file_name = format!("{}/{}{}.png", self.tmp_location, "frame", self.frames.get());
         app.main_window().capture_frame(file_name.as_str());
+        self.frames.set(self.frames.get() + 1);
     }
 
     pub fn finish(&self) {
@@ -102,6 +108,7 @@ pub fn saves_location() -> String {
     std::env::var("SAVES_LOCATION").unwrap_or("../saves/".to_string())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     Right,
     Up,
@@ -119,6 +126,10 @@ impl Direction {
         }
     }
 
+    pub fn opposite(&self) -> Direction {
+        self.next().next()
+    }
+
     pub fn random() -> Direction {
         match random_range(0, 4) {
             0 => Direction::Right,
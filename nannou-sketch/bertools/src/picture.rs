@@ -0,0 +1,253 @@
+use std::path::Path;
+
+use nannou::image::{self, imageops, GrayImage, RgbImage};
+use nannou::prelude::*;
+use nannou::rand::random_f32;
+
+/// Pixels along each side of a cell, when measuring a picture.
+const CELL_PIXELS: u32 = 6;
+
+/// What a cell of a picture looks like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// From 0 for black to 1 for white, after the levels.
+    pub brightness: f32,
+    pub color: Hsla,
+    /// Towards where the picture gets brighter, longer for a sharper change. Up is up, as
+    /// in nannou.
+    pub gradient: Vec2,
+    /// The angle of the edges in the cell, in radians from 0 to PI. Edges run across the
+    /// gradient.
+    pub direction: f32,
+    /// How much the edges in the cell agree on their direction, from 0 for not at all, or
+    /// no edges, to 1 for a single straight edge.
+    pub edge: f32,
+}
+
+/// Contrast: brightness from `black` and below to `white` and above is stretched to the
+/// whole range, then bent by `gamma`. Above 1 darkens the middle, below 1 lightens it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Levels {
+    pub black: f32,
+    pub white: f32,
+    pub gamma: f32,
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self {
+            black: 0.,
+            white: 1.,
+            gamma: 1.,
+        }
+    }
+}
+
+impl Levels {
+    /// Levels like `0.1,0.9` or `0.1,0.9,1.5`: black, white and an optional gamma.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let numbers = spec
+            .split(',')
+            .map(|number| number.trim().parse::<f32>().ok())
+            .collect::<Option<Vec<f32>>>()?;
+        match numbers[..] {
+            [black, white] if black < white => Some(Self {
+                black,
+                white,
+                ..Self::default()
+            }),
+            [black, white, gamma] if black < white && gamma > 0. => Some(Self {
+                black,
+                white,
+                gamma,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, brightness: f32) -> f32 {
+        ((brightness - self.black) / (self.white - self.black))
+            .clamp(0., 1.)
+            .powf(self.gamma)
+    }
+}
+
+/// How to pick from a few choices by a brightness, so that areas in between two choices get
+/// a mix of both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dither {
+    /// The nearest choice, in bands.
+    None,
+    /// Nearest after adding some noise.
+    Random,
+    /// A fixed pattern of thresholds, repeated every 4 cells.
+    Ordered,
+    /// Floyd-Steinberg: whatever is off in one cell is made up for in the next ones.
+    Diffusion,
+}
+
+impl Dither {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Dither::None),
+            "random" => Some(Dither::Random),
+            "ordered" => Some(Dither::Ordered),
+            "diffusion" => Some(Dither::Diffusion),
+            _ => None,
+        }
+    }
+
+    /// For every value from 0 to 1, in rows of `columns`, a choice from 0 to `choices - 1`.
+    pub fn choose(&self, values: &[f32], columns: usize, choices: usize) -> Vec<usize> {
+        let steps = choices.max(1) as f32 - 1.;
+        let nearest = |value: f32| (value.clamp(0., 1.) * steps).round() as usize;
+        // The 4 by 4 Bayer matrix
+        const BAYER: [f32; 16] = [
+            0., 8., 2., 10., 12., 4., 14., 6., 3., 11., 1., 9., 15., 7., 13., 5.,
+        ];
+
+        match self {
+            Dither::None => values.iter().map(|value| nearest(*value)).collect(),
+            Dither::Random => values
+                .iter()
+                .map(|value| nearest(value + (random_f32() - 0.5) / steps.max(1.)))
+                .collect(),
+            Dither::Ordered => values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    let threshold = BAYER[(i / columns % 4) * 4 + i % columns % 4] / 16. + 1. / 32.;
+                    nearest(value + (threshold - 0.5) / steps.max(1.))
+                })
+                .collect(),
+            Dither::Diffusion => {
+                let mut values = values.to_vec();
+                let mut choices = vec![0; values.len()];
+                for i in 0..values.len() {
+                    choices[i] = nearest(values[i]);
+                    let error = values[i] - choices[i] as f32 / steps.max(1.);
+                    let (row, column) = (i / columns, i % columns);
+                    for (d_row, d_column, share) in [
+                        (0, 1, 7. / 16.),
+                        (1, -1, 3. / 16.),
+                        (1, 0, 5. / 16.),
+                        (1, 1, 1. / 16.),
+                    ] {
+                        let column = column as i64 + d_column;
+                        let row = row + d_row;
+                        if column < 0 || column >= columns as i64 {
+                            continue;
+                        }
+                        if let Some(value) = values.get_mut(row * columns + column as usize) {
+                            *value += error * share;
+                        }
+                    }
+                }
+                choices
+            }
+        }
+    }
+}
+
+/// A picture to take brightness, colour and edges from.
+pub struct Picture {
+    pub image: RgbImage,
+}
+
+impl Picture {
+    pub fn open<P: AsRef<Path>>(path: P) -> image::ImageResult<Self> {
+        Ok(Self {
+            image: image::open(path)?.to_rgb8(),
+        })
+    }
+
+    /// The picture cut into `columns` by `rows` cells, a row at a time from the top left.
+    /// The picture is cropped to the shape of the grid, around its middle.
+    pub fn sample(&self, columns: usize, rows: usize, levels: &Levels) -> Vec<Sample> {
+        let (columns, rows) = (columns.max(1) as u32, rows.max(1) as u32);
+        let (width, height) = self.image.dimensions();
+
+        // Crop to the shape of the grid, then shrink so every cell is a few pixels
+        let scale = (width as f32 / columns as f32).min(height as f32 / rows as f32);
+        let (crop_width, crop_height) = (
+            (columns as f32 * scale) as u32,
+            (rows as f32 * scale) as u32,
+        );
+        let cropped = imageops::crop_imm(
+            &self.image,
+            (width - crop_width) / 2,
+            (height - crop_height) / 2,
+            crop_width.max(1),
+            crop_height.max(1),
+        )
+        .to_image();
+        let small = imageops::resize(
+            &cropped,
+            columns * CELL_PIXELS,
+            rows * CELL_PIXELS,
+            imageops::FilterType::Triangle,
+        );
+        let gray: GrayImage = imageops::grayscale(&small);
+        let luma = |x: i64, y: i64| {
+            let x = x.clamp(0, gray.width() as i64 - 1) as u32;
+            let y = y.clamp(0, gray.height() as i64 - 1) as u32;
+            gray.get_pixel(x, y)[0] as f32 / 255.
+        };
+
+        let mut samples = vec![];
+        for row in 0..rows {
+            for column in 0..columns {
+                let (mut red, mut green, mut blue, mut brightness) = (0., 0., 0., 0.);
+                let mut gradient = vec2(0., 0.);
+                // Structure tensor: how strongly the picture changes along x and y
+                let (mut xx, mut yy, mut xy) = (0., 0., 0.);
+                for y in row * CELL_PIXELS..(row + 1) * CELL_PIXELS {
+                    for x in column * CELL_PIXELS..(column + 1) * CELL_PIXELS {
+                        let pixel = small.get_pixel(x, y);
+                        red += pixel[0] as f32;
+                        green += pixel[1] as f32;
+                        blue += pixel[2] as f32;
+
+                        let (x, y) = (x as i64, y as i64);
+                        brightness += luma(x, y);
+                        // Sobel, with y flipped to go up
+                        let dx = (luma(x + 1, y - 1) + 2. * luma(x + 1, y) + luma(x + 1, y + 1))
+                            - (luma(x - 1, y - 1) + 2. * luma(x - 1, y) + luma(x - 1, y + 1));
+                        let dy = (luma(x - 1, y - 1) + 2. * luma(x, y - 1) + luma(x + 1, y - 1))
+                            - (luma(x - 1, y + 1) + 2. * luma(x, y + 1) + luma(x + 1, y + 1));
+                        gradient += vec2(dx, dy);
+                        xx += dx * dx;
+                        yy += dy * dy;
+                        xy += dx * dy;
+                    }
+                }
+
+                let pixels = (CELL_PIXELS * CELL_PIXELS) as f32;
+                let color: Hsl = Srgb::new(
+                    red / pixels / 255.,
+                    green / pixels / 255.,
+                    blue / pixels / 255.,
+                )
+                .into();
+                // The gradient is strongest at this angle, the edges run across it
+                let angle = 0.5 * (2. * xy).atan2(xx - yy);
+                let direction = (angle + PI / 2.).rem_euclid(PI);
+                let edge = match xx + yy {
+                    total if total > f32::EPSILON => {
+                        ((xx - yy).powi(2) + 4. * xy * xy).sqrt() / total
+                    }
+                    _ => 0.,
+                };
+
+                samples.push(Sample {
+                    brightness: levels.apply(brightness / pixels),
+                    color: Hsla::new(color.hue, color.saturation, color.lightness, 1.),
+                    gradient: gradient / pixels,
+                    direction,
+                    edge,
+                });
+            }
+        }
+        samples
+    }
+}
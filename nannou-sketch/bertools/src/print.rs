@@ -0,0 +1,116 @@
+use nannou::prelude::*;
+use nannou::wgpu;
+use nannou::Draw;
+
+use crate::saves_location;
+
+/// Paper sizes in millimeters, portrait.
+const PAPER_SIZES: [(&str, f32, f32); 5] = [
+    ("A2", 420.0, 594.0),
+    ("A3", 297.0, 420.0),
+    ("A4", 210.0, 297.0),
+    ("A5", 148.0, 210.0),
+    ("A6", 105.0, 148.0),
+];
+
+/// Width and height in millimeters of a paper size like `A4`, portrait.
+pub fn paper_size(paper: &str) -> Option<(f32, f32)> {
+    PAPER_SIZES
+        .iter()
+        .find(|(name, _, _)| name.eq_ignore_ascii_case(paper))
+        .map(|(_, width_mm, height_mm)| (*width_mm, *height_mm))
+}
+
+/// Renders a `Draw` to an offscreen texture and saves it as PNG. Unlike `do_save`, the size
+/// of the image is independent of the size of the window.
+pub struct Print {
+    pub width: u32,
+    pub height: u32,
+    texture_capturer: wgpu::TextureCapturer,
+}
+
+impl Print {
+    pub fn new(width: u32, height: u32) -> Self {
+        Print {
+            width,
+            height,
+            texture_capturer: wgpu::TextureCapturer::default(),
+        }
+    }
+
+    /// A print of `width` by `height` millimeters at the given dots per inch.
+    pub fn from_paper(width_mm: f32, height_mm: f32, dpi: f32) -> Self {
+        let to_pixels = |mm: f32| (mm / 25.4 * dpi).round() as u32;
+        Self::new(to_pixels(width_mm), to_pixels(height_mm))
+    }
+
+    /// Parse a paper size with a resolution, like `A3@300`, or a size in pixels, like
+    /// `3508x4961`. Paper sizes default to 300 DPI.
+    pub fn parse(spec: &str) -> Option<Self> {
+        if let Some((width, height)) = spec.split_once('x') {
+            return Some(Self::new(width.parse().ok()?, height.parse().ok()?));
+        }
+
+        let (paper, dpi) = spec.split_once('@').unwrap_or((spec, "300"));
+        let (width_mm, height_mm) = paper_size(paper)?;
+
+        Some(Self::from_paper(width_mm, height_mm, dpi.parse().ok()?))
+    }
+
+    /// Render everything on `draw`, with the origin in the middle of the print, and save it.
+    pub fn capture(&self, app: &App, draw: &Draw) {
+        let window = app.main_window();
+        let device = window.device();
+
+        let texture = wgpu::TextureBuilder::new()
+            .size([self.width, self.height])
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+            .sample_count(window.msaa_samples())
+            .format(wgpu::TextureFormat::Rgba16Float)
+            .build(device);
+        let mut renderer = nannou::draw::RendererBuilder::new()
+            .build_from_texture_descriptor(device, texture.descriptor());
+
+        let ce_desc = wgpu::CommandEncoderDescriptor {
+            label: Some("print renderer"),
+        };
+        let mut encoder = device.create_command_encoder(&ce_desc);
+        renderer.render_to_texture(device, &mut encoder, draw, &texture);
+        let snapshot = self
+            .texture_capturer
+            .capture(device, &mut encoder, &texture);
+        window.queue().submit(Some(encoder.finish()));
+
+        let now = chrono::offset::Local::now();
+        let file_name = format!(
+            "{}{}{}-{}x{}{}",
+            saves_location(),
+            app.exe_name().unwrap(),
+            now.format("%Y-%m-%d-%H-%M-%S"),
+            self.width,
+            self.height,
+            ".png"
+        );
+
+        snapshot
+            .read(move |result| {
+                let image = result.expect("failed to map texture memory").to_owned();
+                image
+                    .save(&file_name)
+                    .expect("failed to save texture to png image");
+                println!("Saved to file://{}", file_name);
+            })
+            .unwrap();
+    }
+
+    /// Wait for prints that are still being written, so they aren't lost when quitting.
+    pub fn finish(&self, app: &App) {
+        let window = app.main_window();
+        if let Err(e) = self
+            .texture_capturer
+            .await_active_snapshots(window.device())
+        {
+            println!("Error finishing prints: {:?}", e);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use lazy_static::lazy_static;
 use nannou::color::Hsla;
+use nannou::rand::Rng;
 
 pub fn navy() -> [Hsla; 3] {
     [
@@ -31,3 +32,20 @@ lazy_static! {
         Hsla::new(30.0, 1.0, 0.6, 1.0),
     ];
 }
+
+/// A slightly different take on a foreground and background color for every seed.
+pub fn seeded_colors(rng: &mut impl Rng, foreground: Hsla, background: Hsla) -> (Hsla, Hsla) {
+    let foreground = Hsla::new(
+        foreground.hue,
+        foreground.saturation * rng.gen_range(0.8..1.0), // random slightly less saturated
+        foreground.lightness * rng.gen_range(1.0..1.2),  // random Slightly more pastel
+        foreground.alpha,
+    );
+    let background = Hsla::new(
+        background.hue,
+        background.saturation * rng.gen_range(0.6..1.0), // random slightly less saturated
+        background.lightness * rng.gen_range(1.0..1.2),  // random slightly darker
+        background.alpha,
+    );
+    (foreground, background)
+}
@@ -0,0 +1,49 @@
+use nannou::color::Hsla;
+use nannou::geom::path::Builder;
+use nannou::geom::Point2;
+use nannou::lyon::path::iterator::PathIterator;
+use nannou::prelude::pt2;
+use nannou::Draw;
+
+/// How far the curves of a shape may stray when they are cut into straight pieces, relative
+/// to its size. Relative, so that a shape drawn small and scaled up stays smooth.
+const TOLERANCE: f32 = 0.0005;
+
+/// A filled heart of `size` wide, centered on `center`.
+pub fn heart(draw: &Draw, center: Point2, size: f32, color: Hsla) {
+    let mut builder = Builder::new().with_svg();
+
+    let half_size = size / 2.0;
+    let quarter_size = size / 4.0;
+    let width_adjustment = 0.6;
+    let height_adjustment = 0.4 * quarter_size;
+
+    // Control points for the Bézier curves
+    let top_center = center + pt2(0.0, quarter_size + height_adjustment);
+    let left_control_1 = center + pt2(-half_size * width_adjustment, half_size + height_adjustment);
+    let left_control_2 = center + pt2(-size, height_adjustment);
+    let bottom_center = center + pt2(0.0, -half_size + height_adjustment);
+    let right_control_1 = center + pt2(size, height_adjustment);
+    let right_control_2 = center + pt2(half_size * width_adjustment, half_size + height_adjustment);
+
+    builder.move_to(top_center.to_array().into());
+
+    // Draw the left half of the heart using Bézier curves
+    builder.cubic_bezier_to(
+        left_control_1.to_array().into(),
+        left_control_2.to_array().into(),
+        bottom_center.to_array().into(),
+    );
+    // Draw the right half of the heart using Bézier curves
+    builder.cubic_bezier_to(
+        right_control_1.to_array().into(),
+        right_control_2.to_array().into(),
+        top_center.to_array().into(),
+    );
+
+    draw.polygon()
+        .stroke_weight(size / 32.0)
+        .stroke(color)
+        .color(color)
+        .events(builder.build().iter().flattened(size * TOLERANCE));
+}
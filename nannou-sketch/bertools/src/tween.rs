@@ -0,0 +1,229 @@
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::hash::{Hash, Hasher};
+
+use nannou::geom::{Point2, Rect};
+use nannou::noise::{self, NoiseFn, Seedable};
+
+/// How far apart the hills of the noise are that `Spread::Noise` starts in, relative to
+/// the diagonal of the area.
+const NOISE_SCALE: f64 = 3.;
+
+/// How a change speeds up and slows down, from 0 at the start to 1 at the end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    /// Slow at the start and at the end.
+    Smooth,
+    /// Slow at the start.
+    In,
+    /// Slow at the end.
+    Out,
+    /// A bit past the end, and back.
+    Back,
+}
+
+impl Easing {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "smooth" => Some(Easing::Smooth),
+            "in" => Some(Easing::In),
+            "out" => Some(Easing::Out),
+            "back" => Some(Easing::Back),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Easing::Linear => Easing::Smooth,
+            Easing::Smooth => Easing::In,
+            Easing::In => Easing::Out,
+            Easing::Out => Easing::Back,
+            Easing::Back => Easing::Linear,
+        }
+    }
+
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Easing::Linear => t,
+            Easing::Smooth => t * t * (3. - 2. * t),
+            Easing::In => t * t * t,
+            Easing::Out => 1. - (1. - t).powi(3),
+            Easing::Back => {
+                const OVERSHOOT: f32 = 1.70158;
+                let t = t - 1.;
+                1. + (OVERSHOOT + 1.) * t * t * t + OVERSHOOT * t * t
+            }
+        }
+    }
+}
+
+/// In which order the changes start, when many things change at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spread {
+    /// All at once.
+    Together,
+    /// In rings, outward from where the change comes from.
+    Ripple,
+    /// From the low to the high parts of a noise field, in patches.
+    Noise,
+}
+
+impl Spread {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "together" => Some(Spread::Together),
+            "ripple" => Some(Spread::Ripple),
+            "noise" => Some(Spread::Noise),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Spread::Together => Spread::Ripple,
+            Spread::Ripple => Spread::Noise,
+            Spread::Noise => Spread::Together,
+        }
+    }
+}
+
+/// How things go from one value to the next: how long each takes, how it eases, and in
+/// which order they start. Timed in frames, not in seconds, so that a recording comes out
+/// the same however fast the frames are drawn.
+pub struct Transition {
+    pub easing: Easing,
+    /// Frames that every change takes.
+    pub duration: u64,
+    pub spread: Spread,
+    /// Frames between the first change to start and the last one.
+    pub stagger: u64,
+    /// Changes started so far, so that every one of them picks anew, see `pick`.
+    pub changes: u64,
+    seed: u64,
+    field: noise::OpenSimplex,
+}
+
+impl Transition {
+    /// The same seed spreads the changes in the same order, and picks the same.
+    pub fn new(seed: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        let seed = hasher.finish();
+        Self {
+            easing: Easing::Smooth,
+            duration: 20,
+            spread: Spread::Ripple,
+            stagger: 40,
+            changes: 0,
+            seed,
+            field: noise::OpenSimplex::new().set_seed(seed as u32),
+        }
+    }
+
+    /// Like `new`, with the easing and spread from EASING and SPREAD, and the frames from
+    /// DURATION and STAGGER, when they are set.
+    pub fn from_env(seed: &str) -> Self {
+        let mut transition = Self::new(seed);
+        // Linear, smooth, in, out or back
+        if let Ok(name) = env::var("EASING") {
+            match Easing::parse(&name) {
+                Some(easing) => transition.easing = easing,
+                None => println!("Can't read the easing {:?}", name),
+            }
+        }
+        // Together, ripple or noise
+        if let Ok(name) = env::var("SPREAD") {
+            match Spread::parse(&name) {
+                Some(spread) => transition.spread = spread,
+                None => println!("Can't read the spread {:?}", name),
+            }
+        }
+        // Frames that every change takes, and between the first and the last to start
+        if let Some(duration) = env::var("DURATION").ok().and_then(|d| d.parse().ok()) {
+            transition.duration = duration;
+        }
+        if let Some(stagger) = env::var("STAGGER").ok().and_then(|s| s.parse().ok()) {
+            transition.stagger = stagger;
+        }
+        transition
+    }
+
+    /// Frames to wait before changing the thing at `position`, when the change comes from
+    /// `origin` and spreads over `area`.
+    pub fn delay(&self, position: Point2, origin: Point2, area: Rect) -> u64 {
+        let reach = [
+            area.top_left(),
+            area.top_right(),
+            area.bottom_left(),
+            area.bottom_right(),
+        ]
+        .iter()
+        .map(|corner| origin.distance(*corner))
+        .fold(0., f32::max)
+        .max(1.);
+        let order = match self.spread {
+            Spread::Together => 0.,
+            Spread::Ripple => position.distance(origin) / reach,
+            Spread::Noise => {
+                let scale = NOISE_SCALE / area.wh().length().max(1.) as f64;
+                let at = [position.x as f64 * scale, position.y as f64 * scale];
+                // The field hardly goes past a half either way
+                self.field.get(at) as f32 + 0.5
+            }
+        };
+        (order.clamp(0., 1.) * self.stagger as f32).round() as u64
+    }
+
+    /// One of `count` things for whatever `key` stands for, like where it is, to change to
+    /// in the current change. Not at random, so that the same seed and the same changes
+    /// record the same every time.
+    pub fn pick<K: Hash>(&self, key: K, count: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        (self.seed, self.changes, key).hash(&mut hasher);
+        (hasher.finish() % count.max(1) as u64) as usize
+    }
+
+    /// From `from` to `to`, starting at `start`.
+    pub fn tween(&self, from: f32, to: f32, start: u64) -> Tween {
+        Tween {
+            from,
+            to,
+            start,
+            duration: self.duration.max(1),
+            easing: self.easing,
+        }
+    }
+}
+
+/// A value on its way from one number to another. Keeps the duration and easing it started
+/// with, when the transition changes halfway.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tween {
+    pub from: f32,
+    pub to: f32,
+    /// The frame it starts moving in.
+    pub start: u64,
+    pub duration: u64,
+    pub easing: Easing,
+}
+
+impl Tween {
+    pub fn value(&self, frame: u64) -> f32 {
+        let t = frame.saturating_sub(self.start) as f32 / self.duration as f32;
+        self.from + (self.to - self.from) * self.easing.apply(t)
+    }
+
+    pub fn done(&self, frame: u64) -> bool {
+        frame >= self.start + self.duration
+    }
+}
+
+/// `to`, give or take whole turns of `turn`, as close to `from` as it gets. To turn the
+/// short way round.
+pub fn nearest(from: f32, to: f32, turn: f32) -> f32 {
+    from + (to - from + turn / 2.).rem_euclid(turn) - turn / 2.
+}
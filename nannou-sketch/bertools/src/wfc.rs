@@ -0,0 +1,722 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BinaryHeap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+
+use nannou::rand::rngs::StdRng;
+use nannou::rand::{Rng, SeedableRng};
+
+use crate::grid::Grid;
+use crate::Direction;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Right,
+    Direction::Up,
+    Direction::Left,
+    Direction::Down,
+];
+
+/// Whether two sides can lie against each other. Sides are read counter-clockwise around
+/// their own tile, so the side next to it is read the other way round. The same socket fits
+/// itself; a socket that looks different backwards ends in `+`, and fits the same name
+/// ending in `-`.
+pub fn fits(socket: &str, other: &str) -> bool {
+    match (socket.strip_suffix('+'), socket.strip_suffix('-')) {
+        (Some(name), _) => other.strip_suffix('-') == Some(name),
+        (_, Some(name)) => other.strip_suffix('+') == Some(name),
+        _ => socket == other,
+    }
+}
+
+/// A kind of tile, by what its sides look like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    /// Of the image, or whatever else the tile is drawn from.
+    pub name: String,
+    /// Right, up, left and down, unturned.
+    pub sockets: [String; 4],
+    /// Quarter turns, counter-clockwise, that the tile may be placed in.
+    pub rotations: Vec<u8>,
+    /// How often to pick it, when it fits as well as the others.
+    pub weight: f32,
+}
+
+impl Tile {
+    /// The socket on the side in `direction`, after `rotation` quarter turns.
+    pub fn socket(&self, direction: Direction, rotation: u8) -> &str {
+        &self.sockets[(direction as usize + 4 - rotation as usize % 4) % 4]
+    }
+}
+
+/// Tiles to arrange, usually from a file with a line for every tile:
+///
+/// ```text
+/// # image                      right  up    left   down  rotations  weight
+/// rainbow_straight/tile_1.png  blank  band  blank  band  0,1        1
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tileset {
+    pub tiles: Vec<Tile>,
+}
+
+impl Tileset {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Skips empty lines, comments from `#`, and lines it can't read.
+    pub fn parse(text: &str) -> Self {
+        let tiles = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| {
+                let tile = Self::parse_tile(line);
+                if tile.is_none() {
+                    println!("Can't read the tile {:?}", line);
+                }
+                tile
+            })
+            .collect();
+        Self { tiles }
+    }
+
+    fn parse_tile(line: &str) -> Option<Tile> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let [name, right, up, left, down, rotations, weight] = words[..] else {
+            return None;
+        };
+        let rotations = rotations
+            .split(',')
+            .map(|rotation| rotation.parse::<u8>().ok().filter(|r| *r < 4))
+            .collect::<Option<Vec<u8>>>()?;
+        Some(Tile {
+            name: name.to_string(),
+            sockets: [right, up, left, down].map(|socket| socket.to_string()),
+            rotations,
+            weight: weight.parse().ok()?,
+        })
+    }
+}
+
+/// A tile in its place: which one of the tileset, and how it is turned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placed {
+    pub tile: usize,
+    pub rotation: u8,
+}
+
+/// Wave function collapse: fills a grid with tiles whose sides fit their neighbours. Every
+/// cell starts out able to hold any tile in any of its turns. The cell with the fewest
+/// left gets one at random, by weight, and that rules out what no longer fits around it.
+/// When a cell runs out of tiles, it takes back choices and tries others, and after too
+/// many of those starts over.
+pub struct Wfc<'a> {
+    pub grid: &'a Grid,
+    pub tileset: &'a Tileset,
+    /// Choices to take back, in one go, before starting over.
+    pub backtracks: usize,
+    /// How often to start over before giving up.
+    pub restarts: usize,
+    rng: StdRng,
+    /// Every tile in every turn, with a weight of more than 0.
+    options: Vec<Placed>,
+    /// For every option, and every direction, which options fit next to it there.
+    fitting: Vec<[Vec<bool>; 4]>,
+    pins: Vec<(usize, Placed)>,
+    // The state of a try
+    allowed: Vec<Vec<bool>>,
+    left: Vec<usize>,
+    /// Options ruled out, in order, to take back.
+    trail: Vec<(usize, usize)>,
+    /// Cells whose options changed, to rule out what no longer fits around them.
+    changed: Vec<usize>,
+    /// Cells by how many options they had left, and a random number to break ties. Only
+    /// those that still have that many count.
+    queue: BinaryHeap<Reverse<(usize, u32, usize)>>,
+}
+
+impl<'a> Wfc<'a> {
+    /// The same seed arranges the same tiles on the same grid the same way.
+    pub fn new(grid: &'a Grid, tileset: &'a Tileset, seed: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+
+        let options: Vec<Placed> = tileset
+            .tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.weight > 0.)
+            .flat_map(|(i, tile)| {
+                tile.rotations.iter().map(move |rotation| Placed {
+                    tile: i,
+                    rotation: *rotation,
+                })
+            })
+            .collect();
+        let socket = |option: &Placed, direction: Direction| {
+            tileset.tiles[option.tile].socket(direction, option.rotation)
+        };
+        let fitting = options
+            .iter()
+            .map(|option| {
+                DIRECTIONS.map(|direction| {
+                    options
+                        .iter()
+                        .map(|other| {
+                            fits(
+                                socket(option, direction),
+                                socket(other, direction.opposite()),
+                            )
+                        })
+                        .collect()
+                })
+            })
+            .collect();
+
+        Self {
+            grid,
+            tileset,
+            backtracks: 1000,
+            restarts: 10,
+            rng: StdRng::seed_from_u64(hasher.finish()),
+            options,
+            fitting,
+            pins: vec![],
+            allowed: vec![],
+            left: vec![],
+            trail: vec![],
+            changed: vec![],
+            queue: BinaryHeap::new(),
+        }
+    }
+
+    /// Always put `placed` in the cell at `index`, and fit the rest around it.
+    pub fn pin(&mut self, index: usize, placed: Placed) {
+        self.pins.push((index, placed));
+    }
+
+    /// A tile for every cell of the grid, a row at a time from the top left. None when the
+    /// tiles can't be made to fit, or the pins don't.
+    pub fn solve(&mut self) -> Option<Vec<Placed>> {
+        if self.options.is_empty() || self.grid.is_empty() {
+            println!("Nothing to arrange");
+            return None;
+        }
+        for attempt in 0..=self.restarts {
+            if !self.start() {
+                println!("The tiles don't fit together, or the pins don't");
+                return None;
+            }
+            if let Some(solution) = self.attempt() {
+                return Some(solution);
+            }
+            println!(
+                "No fit after {} backtracks, starting over ({})",
+                self.backtracks,
+                attempt + 1
+            );
+        }
+        None
+    }
+
+    /// Every option everywhere, but for the pinned cells. False when the pins contradict.
+    fn start(&mut self) -> bool {
+        let cells = self.grid.len();
+        self.allowed = vec![vec![true; self.options.len()]; cells];
+        self.left = vec![self.options.len(); cells];
+        self.trail = vec![];
+        self.changed = vec![];
+
+        for (index, placed) in self.pins.clone() {
+            let Some(keep) = self.options.iter().position(|option| *option == placed) else {
+                return false;
+            };
+            if index >= cells || !self.allowed[index][keep] {
+                return false;
+            }
+            for option in 0..self.options.len() {
+                if option != keep && !self.rule_out(index, option) {
+                    return false;
+                }
+            }
+        }
+        // What fits next to what, everywhere, also when there is nothing to choose
+        self.changed = (0..cells).collect();
+        let settled = self.propagate();
+        // Pins are not taken back
+        self.trail = vec![];
+        self.queue.clear();
+        for cell in 0..cells {
+            self.enqueue(cell);
+        }
+        settled
+    }
+
+    fn attempt(&mut self) -> Option<Vec<Placed>> {
+        // Where the trail was before each choice, and the choice
+        let mut choices: Vec<(usize, usize, usize)> = vec![];
+        let mut backtracks = 0;
+        let mut settled = true;
+
+        loop {
+            if !settled {
+                backtracks += 1;
+                let (mark, cell, option) = choices.pop()?;
+                if backtracks > self.backtracks {
+                    return None;
+                }
+                self.undo(mark);
+                // Try anything but that choice
+                settled = self.rule_out(cell, option) && self.propagate();
+                continue;
+            }
+
+            let Some(cell) = self.least_left() else {
+                return Some(
+                    self.allowed
+                        .iter()
+                        .map(|allowed| self.options[allowed.iter().position(|a| *a).unwrap()])
+                        .collect(),
+                );
+            };
+            let option = self.pick(cell);
+            choices.push((self.trail.len(), cell, option));
+            settled = (0..self.options.len())
+                .filter(|other| *other != option)
+                .all(|other| self.rule_out(cell, other))
+                && self.propagate();
+        }
+    }
+
+    /// A cell that still has more than one option, of those with the fewest.
+    fn least_left(&mut self) -> Option<usize> {
+        while let Some(Reverse((left, _, cell))) = self.queue.pop() {
+            if left == self.left[cell] && left > 1 {
+                return Some(cell);
+            }
+        }
+        None
+    }
+
+    fn enqueue(&mut self, cell: usize) {
+        if self.left[cell] > 1 {
+            self.queue
+                .push(Reverse((self.left[cell], self.rng.gen(), cell)));
+        }
+    }
+
+    /// One of the options left in `cell`, by weight. Tiles with more turns get each turn
+    /// less often, so every tile gets its own weight.
+    fn pick(&mut self, cell: usize) -> usize {
+        let weight = |option: &Placed| {
+            let tile = &self.tileset.tiles[option.tile];
+            tile.weight / tile.rotations.len() as f32
+        };
+        let left: Vec<usize> = (0..self.options.len())
+            .filter(|option| self.allowed[cell][*option])
+            .collect();
+        let total: f32 = left
+            .iter()
+            .map(|option| weight(&self.options[*option]))
+            .sum();
+        let mut chance = self.rng.gen_range(0.0..total);
+        for option in &left {
+            let weight = weight(&self.options[*option]);
+            if chance < weight {
+                return *option;
+            }
+            chance -= weight;
+        }
+        // Only when rounding leaves a tiny bit of chance
+        *left.last().unwrap()
+    }
+
+    /// False when that leaves the cell with nothing.
+    fn rule_out(&mut self, cell: usize, option: usize) -> bool {
+        if self.allowed[cell][option] {
+            self.allowed[cell][option] = false;
+            self.left[cell] -= 1;
+            self.trail.push((cell, option));
+            self.changed.push(cell);
+            self.enqueue(cell);
+        }
+        self.left[cell] > 0
+    }
+
+    /// Rule out, around every changed cell, the options that fit none of what it has left,
+    /// and so on. False when a cell runs out of options.
+    fn propagate(&mut self) -> bool {
+        while let Some(cell) = self.changed.pop() {
+            for direction in DIRECTIONS {
+                let Some(neighbour) = self.grid.neighbour(cell, direction) else {
+                    continue;
+                };
+                for other in 0..self.options.len() {
+                    if !self.allowed[neighbour][other] {
+                        continue;
+                    }
+                    let supported = (0..self.options.len()).any(|option| {
+                        self.allowed[cell][option]
+                            && self.fitting[option][direction as usize][other]
+                    });
+                    if !supported && !self.rule_out(neighbour, other) {
+                        self.changed = vec![];
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Allow again what was ruled out since the trail was `mark` long.
+    fn undo(&mut self, mark: usize) {
+        let restored: Vec<(usize, usize)> = self.trail.drain(mark..).collect();
+        for (cell, option) in restored {
+            self.allowed[cell][option] = true;
+            self.left[cell] += 1;
+            self.enqueue(cell);
+        }
+        self.changed = vec![];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(name: &str, sockets: [&str; 4], rotations: &[u8]) -> Tile {
+        Tile {
+            name: name.to_string(),
+            sockets: sockets.map(|socket| socket.to_string()),
+            rotations: rotations.to_vec(),
+            weight: 1.,
+        }
+    }
+
+    /// Every side fits the side next to it.
+    fn assert_fits(grid: &Grid, tileset: &Tileset, solution: &[Placed]) {
+        assert_eq!(solution.len(), grid.len());
+        for (cell, placed) in solution.iter().enumerate() {
+            for direction in DIRECTIONS {
+                let Some(neighbour) = grid.neighbour(cell, direction) else {
+                    continue;
+                };
+                let other = solution[neighbour];
+                let socket = tileset.tiles[placed.tile].socket(direction, placed.rotation);
+                let facing = tileset.tiles[other.tile].socket(direction.opposite(), other.rotation);
+                assert!(
+                    fits(socket, facing),
+                    "{} at {} next to {}",
+                    socket,
+                    cell,
+                    facing
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fits_itself_and_the_other_way_round() {
+        assert!(fits("band", "band"));
+        assert!(!fits("band", "blank"));
+        assert!(fits("half+", "half-"));
+        assert!(fits("half-", "half+"));
+        assert!(!fits("half+", "half+"));
+        assert!(!fits("half+", "half"));
+        assert!(!fits("half", "half-"));
+        assert!(!fits("half+", "band-"));
+    }
+
+    #[test]
+    fn sockets_turn_with_the_tile() {
+        let tile = tile("turned", ["right", "up", "left", "down"], &[0, 1, 2, 3]);
+        assert_eq!(tile.socket(Direction::Right, 0), "right");
+        assert_eq!(tile.socket(Direction::Down, 0), "down");
+        // A quarter turn counter-clockwise puts the right side up
+        assert_eq!(tile.socket(Direction::Up, 1), "right");
+        assert_eq!(tile.socket(Direction::Right, 1), "down");
+        assert_eq!(tile.socket(Direction::Left, 2), "right");
+        assert_eq!(tile.socket(Direction::Down, 3), "right");
+        assert_eq!(tile.socket(Direction::Up, 4), "up");
+    }
+
+    #[test]
+    fn parses_tiles_and_skips_the_rest() {
+        let tileset = Tileset::parse(
+            "# image  right up left down rotations weight\n\
+             \n\
+             a.png  band  blank  band  blank  0,1  2  # straight\n\
+             b.png  band  band  blank  blank  0,1,2,3\n\
+             c.png  band  band  blank  blank  0,4  1\n\
+             d.png  half+  half-  band  band  0  0.5\n",
+        );
+        assert_eq!(tileset.tiles.len(), 2);
+        assert_eq!(
+            tileset.tiles[0],
+            Tile {
+                weight: 2.,
+                ..tile("a.png", ["band", "blank", "band", "blank"], &[0, 1])
+            }
+        );
+        assert_eq!(tileset.tiles[1].sockets[0], "half+");
+        assert_eq!(tileset.tiles[1].weight, 0.5);
+    }
+
+    #[test]
+    fn every_rainbow_side_has_a_match() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/rainbow.tiles");
+        let tileset = Tileset::open(path).unwrap();
+        assert_eq!(tileset.tiles.len(), 4);
+        let sockets: Vec<&String> = tileset.tiles.iter().flat_map(|t| &t.sockets).collect();
+        for socket in &sockets {
+            assert!(
+                sockets.iter().any(|other| fits(socket, other)),
+                "{}",
+                socket
+            );
+        }
+    }
+
+    #[test]
+    fn arranges_tiles_that_fit() {
+        // Lines that never end: straights, corners and crossings, and empty tiles
+        let tileset = Tileset {
+            tiles: vec![
+                tile("empty", ["blank", "blank", "blank", "blank"], &[0]),
+                tile("straight", ["line", "blank", "line", "blank"], &[0, 1]),
+                tile("corner", ["line", "line", "blank", "blank"], &[0, 1, 2, 3]),
+                tile("cross", ["line", "line", "line", "line"], &[0]),
+                tile("arrow", ["in+", "blank", "in-", "blank"], &[0, 1, 2, 3]),
+            ],
+        };
+        let grid = Grid::new(12, 9, 1.);
+        for seed in 0..20 {
+            let solution = Wfc::new(&grid, &tileset, &seed.to_string())
+                .solve()
+                .unwrap();
+            assert_fits(&grid, &tileset, &solution);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_arranges_the_same_way() {
+        let tileset = Tileset {
+            tiles: vec![
+                tile("straight", ["line", "blank", "line", "blank"], &[0, 1]),
+                tile("corner", ["line", "line", "blank", "blank"], &[0, 1, 2, 3]),
+            ],
+        };
+        let grid = Grid::new(6, 6, 1.);
+        let first = Wfc::new(&grid, &tileset, "seed").solve();
+        assert!(first.is_some());
+        assert_eq!(first, Wfc::new(&grid, &tileset, "seed").solve());
+    }
+
+    #[test]
+    fn a_tile_spreads_to_what_must_match_it() {
+        // Red only fits red and blue only fits blue, so one red tile makes them all red
+        let tileset = Tileset {
+            tiles: vec![
+                tile("red", ["red", "red", "red", "red"], &[0]),
+                tile("blue", ["blue", "blue", "blue", "blue"], &[0]),
+            ],
+        };
+        let grid = Grid::new(5, 4, 1.);
+        let red = Placed {
+            tile: 0,
+            rotation: 0,
+        };
+        let mut wfc = Wfc::new(&grid, &tileset, "seed");
+        wfc.pin(grid.index(3, 2), red);
+        assert_eq!(wfc.solve(), Some(vec![red; grid.len()]));
+    }
+
+    #[test]
+    fn pinned_tiles_stay_as_they_are_set() {
+        let tileset = Tileset {
+            tiles: vec![
+                tile("empty", ["blank", "blank", "blank", "blank"], &[0]),
+                tile("straight", ["line", "blank", "line", "blank"], &[0, 1]),
+                tile("corner", ["line", "line", "blank", "blank"], &[0, 1, 2, 3]),
+            ],
+        };
+        let grid = Grid::new(8, 8, 1.);
+        let pins = [
+            (
+                grid.index(0, 0),
+                Placed {
+                    tile: 2,
+                    rotation: 3,
+                },
+            ),
+            (
+                grid.index(4, 4),
+                Placed {
+                    tile: 1,
+                    rotation: 1,
+                },
+            ),
+            (
+                grid.index(7, 2),
+                Placed {
+                    tile: 2,
+                    rotation: 1,
+                },
+            ),
+        ];
+        for seed in 0..10 {
+            let mut wfc = Wfc::new(&grid, &tileset, &seed.to_string());
+            // Start over every time, so the pins have to come back too
+            wfc.backtracks = 0;
+            wfc.restarts = 100;
+            pins.iter()
+                .for_each(|(index, placed)| wfc.pin(*index, *placed));
+            let solution = wfc.solve().unwrap();
+            assert_fits(&grid, &tileset, &solution);
+            for (index, placed) in pins {
+                assert_eq!(solution[index], placed);
+            }
+        }
+    }
+
+    #[test]
+    fn pins_that_dont_fit_give_nothing() {
+        let tileset = Tileset {
+            tiles: vec![
+                tile("empty", ["blank", "blank", "blank", "blank"], &[0]),
+                tile("straight", ["line", "blank", "line", "blank"], &[0, 1]),
+            ],
+        };
+        let grid = Grid::new(3, 3, 1.);
+        let mut wfc = Wfc::new(&grid, &tileset, "seed");
+        wfc.pin(
+            0,
+            Placed {
+                tile: 0,
+                rotation: 0,
+            },
+        );
+        wfc.pin(
+            1,
+            Placed {
+                tile: 1,
+                rotation: 0,
+            },
+        );
+        assert_eq!(wfc.solve(), None);
+
+        // Not in the tileset, or outside the grid
+        let mut wfc = Wfc::new(&grid, &tileset, "seed");
+        wfc.pin(
+            0,
+            Placed {
+                tile: 0,
+                rotation: 1,
+            },
+        );
+        assert_eq!(wfc.solve(), None);
+        let mut wfc = Wfc::new(&grid, &tileset, "seed");
+        wfc.pin(
+            9,
+            Placed {
+                tile: 0,
+                rotation: 0,
+            },
+        );
+        assert_eq!(wfc.solve(), None);
+    }
+
+    #[test]
+    fn tiles_that_cant_fit_give_nothing() {
+        // A side that only fits the other way round, on every side of the only tile
+        let tileset = Tileset {
+            tiles: vec![tile("one way", ["in+", "in+", "in+", "in+"], &[0, 1, 2, 3])],
+        };
+        let grid = Grid::new(2, 2, 1.);
+        assert_eq!(Wfc::new(&grid, &tileset, "seed").solve(), None);
+        // With nothing to choose from, so there is nothing to take back
+        let tileset = Tileset {
+            tiles: vec![tile("one way", ["in+", "in+", "in+", "in+"], &[0])],
+        };
+        assert_eq!(Wfc::new(&grid, &tileset, "seed").solve(), None);
+
+        // Lines that have to end at the edge, but no tile ends a line
+        let tileset = Tileset {
+            tiles: vec![tile("cross", ["line", "line", "line", "line"], &[0])],
+        };
+        let grid = Grid::new(3, 3, 1.);
+        assert!(Wfc::new(&grid, &tileset, "seed").solve().is_some());
+        let tileset = Tileset {
+            tiles: vec![
+                tile("straight", ["line", "blank", "line", "blank"], &[0, 1]),
+                tile("cross", ["line", "line", "line", "line"], &[0]),
+            ],
+        };
+        assert!(Wfc::new(&grid, &tileset, "seed").solve().is_some());
+        assert_eq!(Wfc::new(&grid, &Tileset::default(), "seed").solve(), None);
+    }
+
+    #[test]
+    fn backtracking_gives_back_what_was_ruled_out() {
+        let tileset = Tileset {
+            tiles: vec![
+                tile("empty", ["blank", "blank", "blank", "blank"], &[0]),
+                tile("straight", ["line", "blank", "line", "blank"], &[0, 1]),
+                tile("corner", ["line", "line", "blank", "blank"], &[0, 1, 2, 3]),
+            ],
+        };
+        let grid = Grid::new(4, 4, 1.);
+        let mut wfc = Wfc::new(&grid, &tileset, "seed");
+        assert!(wfc.start());
+        let (allowed, left) = (wfc.allowed.clone(), wfc.left.clone());
+
+        // Only empty tiles in one cell, and around it what fits that
+        let cell = grid.index(1, 1);
+        let mark = wfc.trail.len();
+        assert!((1..wfc.options.len()).all(|option| wfc.rule_out(cell, option)));
+        assert!(wfc.propagate());
+        assert_eq!(wfc.left[cell], 1);
+        assert!(wfc.left[grid.index(2, 1)] < left[grid.index(2, 1)]);
+
+        wfc.undo(mark);
+        assert_eq!(wfc.allowed, allowed);
+        assert_eq!(wfc.left, left);
+    }
+
+    #[test]
+    fn backtracks_out_of_dead_ends() {
+        // Wang tiles, that can't be turned: choosing by the fewest options left runs into
+        // cells that nothing fits most of the time, and has to take choices back
+        let tileset = Tileset {
+            tiles: vec![
+                tile("a", ["r", "g", "g", "r"], &[0]),
+                tile("b", ["g", "b", "r", "b"], &[0]),
+                tile("c", ["b", "r", "g", "g"], &[0]),
+                tile("d", ["r", "b", "b", "g"], &[0]),
+                tile("e", ["g", "g", "b", "r"], &[0]),
+                tile("f", ["b", "b", "r", "r"], &[0]),
+            ],
+        };
+        let grid = Grid::new(8, 8, 1.);
+        let mut stuck = 0;
+        for seed in 0..20 {
+            let mut wfc = Wfc::new(&grid, &tileset, &seed.to_string());
+            wfc.backtracks = 0;
+            wfc.restarts = 0;
+            if wfc.solve().is_none() {
+                stuck += 1;
+            }
+
+            let mut wfc = Wfc::new(&grid, &tileset, &seed.to_string());
+            wfc.restarts = 0;
+            let solution = wfc.solve().unwrap();
+            assert_fits(&grid, &tileset, &solution);
+        }
+        assert!(stuck > 0);
+    }
+}
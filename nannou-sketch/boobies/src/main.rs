@@ -1,9 +1,13 @@
+use std::env;
+use std::path::PathBuf;
+
 use nannou::{
     prelude::*,
     rand::{seq::SliceRandom, thread_rng},
 };
 
 use bertools::do_save;
+use bertools::picture::{Dither, Levels, Picture};
 use bertools::Nannou;
 
 fn main() {
@@ -14,8 +18,32 @@ fn main() {
         .run();
 }
 
-fn model(_app: &App) -> Model {
-    Model::default()
+fn model(app: &App) -> Model {
+    let mut model = Model::default();
+    // Follow a picture, like `example.png` from the assets folder, or any other path
+    if let Ok(name) = env::var("IMAGE") {
+        let mut path = PathBuf::from(&name);
+        if !path.exists() {
+            path = app.assets_path().unwrap().join(&name);
+        }
+        match Picture::open(&path) {
+            Ok(picture) => model.picture = Some(picture),
+            Err(e) => println!("Error opening {:?}: {:?}", path, e),
+        }
+        // Black and white point, and gamma, like `0.1,0.9,1.5`
+        if let Ok(levels) = env::var("LEVELS") {
+            match Levels::parse(&levels) {
+                Some(levels) => model.levels = levels,
+                None => println!("Can't read the levels {:?}", levels),
+            }
+        }
+        // None, random, ordered or diffusion
+        model.dither = env::var("DITHER")
+            .ok()
+            .and_then(|name| Dither::parse(&name))
+            .unwrap_or(Dither::Diffusion);
+    }
+    model
 }
 
 fn event(app: &App, _model: &mut Model, event: Event) {
@@ -29,7 +57,8 @@ fn event(app: &App, _model: &mut Model, event: Event) {
     }
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
+fn update(app: &App, model: &mut Model, _update: Update) {
+    model.follow_picture(app.window_rect());
     model.update();
 }
 
@@ -45,12 +74,20 @@ fn view(app: &App, model: &Model, frame: Frame) {
 struct Model {
     background_color: Hsla,
     tiles: Vec<Tile>,
+    /// Turns, weighs and colours the tiles, instead of chance.
+    picture: Option<Picture>,
+    levels: Levels,
+    dither: Dither,
+    /// Of the grid the picture was last followed for. 0 when it hasn't been yet.
+    columns: usize,
 }
 
 #[derive(Debug)]
 struct Tile {
     line_color: Hsla,
     orientation: f32,
+    weight: f32,
+    dots: bool,
 }
 
 impl Default for Model {
@@ -61,11 +98,48 @@ impl Default for Model {
             .map(|_| Tile {
                 orientation: *angles.choose(&mut rng).unwrap(),
                 line_color: Hsla::new(100., 0.5, 0.1, 1.),
+                weight: 2.,
+                dots: true,
             })
             .collect();
         Self {
             background_color: Hsla::new(200., 0.0, 0.9, 1.),
             tiles,
+            picture: None,
+            levels: Levels::default(),
+            dither: Dither::Diffusion,
+            columns: 0,
+        }
+    }
+}
+
+impl Model {
+    /// Turn, weigh and colour the tiles in `window` after the picture, when there is one and
+    /// the grid changed since the last time. Darker cells get thicker lines and dots, and
+    /// the half circles bulge towards where the picture gets brighter.
+    fn follow_picture(&mut self, window: Rect) {
+        let Some(picture) = &self.picture else {
+            return;
+        };
+        let columns = ((window.w() / TILE_SIZE) + 1.) as usize;
+        if columns == self.columns {
+            return;
+        }
+        self.columns = columns;
+        let rows = ((window.h() / TILE_SIZE) + 1.) as usize;
+        let samples = picture.sample(columns, rows, &self.levels);
+        let brightness: Vec<f32> = samples.iter().map(|s| s.brightness).collect();
+        let dots = self.dither.choose(&brightness, columns, 2);
+
+        for ((tile, sample), choice) in self.tiles.iter_mut().zip(&samples).zip(dots) {
+            tile.line_color = sample.color;
+            tile.weight = 0.5 + 3.5 * (1. - sample.brightness);
+            tile.dots = choice == 0;
+            // Too flat to tell which way it gets brighter, keep the turn it has
+            if sample.gradient.length() > GRADIENT {
+                let angle = rad_to_deg(sample.gradient.y.atan2(sample.gradient.x)) - 90.;
+                tile.orientation = ((angle / 90.).round() * 90.).rem_euclid(360.);
+            }
         }
     }
 }
@@ -94,6 +168,8 @@ impl Nannou for Model {
 const N_TILES: usize = 1000;
 const RESOLUTION: usize = 300;
 const TILE_SIZE: f32 = 60.;
+/// Below this, a cell of the picture is too flat to turn a tile by.
+const GRADIENT: f32 = 0.1;
 
 impl Nannou for Tile {
     fn view(&self, _app: &App, draw: &Draw) {
@@ -117,10 +193,13 @@ impl Nannou for Tile {
         });
 
         draw.polyline()
-            .weight(2.)
+            .weight(self.weight)
             .points(points)
             .color(self.line_color);
 
+        if !self.dots {
+            return;
+        }
         draw.ellipse()
             .x_y(ellipse_center.x, ellipse_center.y)
             .radius(TILE_SIZE / 8.)
@@ -0,0 +1,165 @@
+use std::ops::Range;
+
+use nannou::prelude::*;
+use nannou::rand::thread_rng;
+
+use crate::models::{Model, Tile};
+
+/// How far the camera zooms out and in.
+const ZOOM: Range<f32> = 0.05..20.;
+/// Tiles in view at most, zoomed all the way out. More are slow to make and to draw.
+const MOST_TILES: f32 = 10_000.;
+
+/// What part of the tiles is in the window. The tiles are laid out from the middle of the
+/// window at the start, in pixels, which the camera moves over and scales.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// The point of the tiles in the middle of the window.
+    pub center: Point2,
+    pub zoom: f32,
+    /// Where the mouse was last, while dragging.
+    pub drag: Option<Point2>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            center: pt2(0., 0.),
+            zoom: 1.,
+            drag: None,
+        }
+    }
+}
+
+impl Camera {
+    pub fn on_screen(&self, point: Point2) -> Point2 {
+        (point - self.center) * self.zoom
+    }
+
+    pub fn on_tiles(&self, point: Point2) -> Point2 {
+        point / self.zoom + self.center
+    }
+
+    /// The part of the tiles in `window`.
+    pub fn visible(&self, window: Rect) -> Rect {
+        Rect::from_corners(
+            self.on_tiles(window.bottom_left()),
+            self.on_tiles(window.top_right()),
+        )
+    }
+
+    /// Draw the tiles through the camera.
+    pub fn transform(&self, draw: &Draw) -> Draw {
+        draw.scale(self.zoom).xy(-self.center)
+    }
+
+    /// Move the tiles along with the mouse, `by` pixels on the screen.
+    pub fn pan(&mut self, by: Vec2) {
+        self.center -= by / self.zoom;
+    }
+
+    /// Zoom in by `factor`, or out below 1 but not below `least`, keeping the tiles under
+    /// `at` on the screen in place.
+    pub fn zoom_at(&mut self, factor: f32, at: Point2, least: f32) {
+        let before = self.on_tiles(at);
+        self.zoom = (self.zoom * factor).clamp(least.max(ZOOM.start), ZOOM.end);
+        self.center += before - self.on_tiles(at);
+    }
+}
+
+impl Model {
+    /// The columns and rows of the tiles the camera sees in `window`. Rows go down.
+    pub fn region(&self, camera: &Camera, window: Rect) -> (Range<i64>, Range<i64>) {
+        let visible = camera.visible(window);
+        let columns = (visible.left() / self.tile_size).floor() as i64
+            ..(visible.right() / self.tile_size).ceil() as i64;
+        let rows = (-visible.top() / self.tile_size).floor() as i64
+            ..(-visible.bottom() / self.tile_size).ceil() as i64;
+        (columns, rows)
+    }
+
+    /// How far the camera can zoom out on `window` before it sees too many tiles.
+    pub fn least_zoom(&self, window: Rect) -> f32 {
+        (window.w() * window.h() / (MOST_TILES * self.tile_size * self.tile_size)).sqrt()
+    }
+
+    /// Where the centre of the tile at `column` and `row` is.
+    pub fn position(&self, column: i64, row: i64) -> Point2 {
+        pt2(column as f32 + 0.5, -(row as f32) - 0.5) * self.tile_size
+    }
+
+    /// A new tile from the registry, at the size of the others.
+    pub fn new_tile(&self) -> Tile {
+        let rng = &mut thread_rng();
+        let mut tile = Tile::new(self.registry.pick(rng), rng);
+        tile.tile_size = self.tile_size;
+        tile
+    }
+
+    /// The tiles in `window`, each with where its centre goes, a row at a time from the top
+    /// left. Also the size of the tiles.
+    pub fn placed(&self, window: Rect) -> (f32, Vec<(Point2, &Tile)>) {
+        let (columns, rows) = self.region(&self.camera, window);
+        let placed = rows
+            .flat_map(|row| columns.clone().map(move |column| (column, row)))
+            .filter_map(|(column, row)| {
+                let tile = self.tiles.get(&(column, row))?;
+                Some((self.position(column, row), tile))
+            })
+            .collect();
+        (self.tile_size, placed)
+    }
+
+    /// Make the tiles that came into view in `window` since the last time, and that don't
+    /// exist yet. Tiles once made stay where they are, wherever the camera goes.
+    pub fn fill(&mut self, window: Rect) {
+        // The tiles may have got smaller since
+        self.camera.zoom = self.camera.zoom.max(self.least_zoom(window));
+        let region = self.region(&self.camera, window);
+        if self.filled.as_ref() == Some(&region) {
+            return;
+        }
+        let fresh = match self.filled.take() {
+            Some(before) => uncovered(&region, &before),
+            None => vec![region.clone()],
+        };
+
+        for (columns, rows) in fresh {
+            self.collapse(columns.clone(), rows.clone());
+            for row in rows {
+                for column in columns.clone() {
+                    if !self.tiles.contains_key(&(column, row)) {
+                        let tile = self.new_tile();
+                        self.tiles.insert((column, row), tile);
+                    }
+                }
+            }
+        }
+        self.filled = Some(region);
+    }
+}
+
+/// The parts of `region` outside of `before`: the rows above and below it, and the columns
+/// left and right of it in between.
+fn uncovered(
+    region: &(Range<i64>, Range<i64>),
+    before: &(Range<i64>, Range<i64>),
+) -> Vec<(Range<i64>, Range<i64>)> {
+    let (columns, rows) = region;
+    let clamp_row = |row: i64| row.clamp(rows.start, rows.end);
+    let clamp_column = |column: i64| column.clamp(columns.start, columns.end);
+    let above = rows.start..clamp_row(before.1.start);
+    let below = clamp_row(before.1.end)..rows.end;
+    let between = above.end..below.start;
+    let left = columns.start..clamp_column(before.0.start);
+    let right = clamp_column(before.0.end)..columns.end;
+    [
+        (columns.clone(), above),
+        (columns.clone(), below),
+        (left, between.clone()),
+        (right, between),
+    ]
+    .into_iter()
+    .filter(|(columns, rows)| !columns.is_empty() && !rows.is_empty())
+    .collect()
+}
@@ -0,0 +1,89 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use bertools::grid::Grid;
+use bertools::wfc::{self, Placed, Tileset, Wfc};
+
+use crate::models::Model;
+use crate::tiles::TileFamily;
+
+impl Model {
+    /// Pick and turn the tiles in `columns` and `rows` that don't exist yet, so their lines
+    /// meet the lines of the tiles next to them, with wave function collapse, when there is
+    /// a seed for it. The tiles that do exist stay as they are, and the new ones fit around
+    /// them.
+    pub fn collapse(&mut self, columns: Range<i64>, rows: Range<i64>) {
+        let Some(seed) = &self.wfc else {
+            return;
+        };
+        // One more all around, to fit the tiles just outside
+        let (columns, rows) = (
+            columns.start - 1..columns.end + 1,
+            rows.start - 1..rows.end + 1,
+        );
+        let cells: Vec<(i64, i64)> = rows
+            .clone()
+            .flat_map(|row| columns.clone().map(move |column| (column, row)))
+            .collect();
+        if cells.iter().all(|cell| self.tiles.contains_key(cell)) {
+            return;
+        }
+        let grid = Grid::new(
+            (columns.end - columns.start) as usize,
+            (rows.end - rows.start) as usize,
+            self.tile_size,
+        );
+
+        let families: Vec<(Rc<dyn TileFamily>, f32)> = self
+            .registry
+            .families
+            .iter()
+            .filter(|(_, weight)| *weight > 0.)
+            .cloned()
+            .collect();
+        let tileset = Tileset {
+            tiles: families
+                .iter()
+                .map(|(family, weight)| wfc::Tile {
+                    name: family.name().to_string(),
+                    sockets: family.sockets(),
+                    rotations: family.rotations(),
+                    weight: *weight,
+                })
+                .collect(),
+        };
+
+        let mut solver = Wfc::new(&grid, &tileset, seed);
+        for (index, cell) in cells.iter().enumerate() {
+            let Some(tile) = self.tiles.get(cell) else {
+                continue;
+            };
+            if let Some(family) = families
+                .iter()
+                .position(|(family, _)| family.name() == tile.family.name())
+            {
+                solver.pin(
+                    index,
+                    Placed {
+                        tile: family,
+                        rotation: tile.orientation,
+                    },
+                );
+            }
+        }
+
+        match solver.solve() {
+            Some(placed) => {
+                for (cell, placed) in cells.into_iter().zip(placed) {
+                    if !self.tiles.contains_key(&cell) {
+                        let mut tile = self.new_tile();
+                        tile.family = families[placed.tile].0.clone();
+                        tile.orientation = placed.rotation;
+                        self.tiles.insert(cell, tile);
+                    }
+                }
+            }
+            None => println!("Can't make these tiles fit, picking them at random"),
+        }
+    }
+}
@@ -1,21 +1,33 @@
-use models::TileType;
+use std::env;
+use std::path::PathBuf;
+use std::rc::Rc;
+
 use nannou::prelude::*;
 
+mod camera;
+mod collapse;
 mod models;
+mod paths;
+mod picture;
+mod quadtree;
+mod tiles;
+mod turns;
 use crate::models::{Model, Tile};
+use crate::paths::Coloring;
+use crate::quadtree::{Quadtree, Subdivision};
+use crate::tiles::{Registry, Rounded};
 use bertools::do_save;
-use bertools::Nannou;
+use bertools::picture::{Dither, Levels, Picture};
+use bertools::tween::Transition;
+use bertools::{Nannou, Record};
 
-const LINE_FACTOR: f32 = 0.15;
+pub const LINE_FACTOR: f32 = 0.15;
+/// How much a line of the mouse wheel zooms in.
+const ZOOM_STEP: f32 = 1.1;
 
 impl Default for Model {
     fn default() -> Self {
-        let n_tiles = 4000;
-        let background_color = hsla(0., 0., 0.04, 1.0);
-        Self {
-            background_color,
-            tiles: Tile::n_instances(n_tiles),
-        }
+        Self::new(Registry::default())
     }
 }
 
@@ -25,9 +37,12 @@ impl Default for Tile {
             // line_color: hsla(0., 0.15, 0.14, 1.0),
             line_color: hsla(0.8333, 0.4, 0.5, 1.0),
             orientation: 0,
+            angle: 0.,
+            turning: None,
+            line_weight: LINE_FACTOR,
             tile_size: 800.,
             resolution: 100,
-            tile_type: TileType::Rounded,
+            family: Rc::new(Rounded),
         }
     }
 }
@@ -49,7 +64,100 @@ fn model(app: &App) -> Model {
         .build()
         .unwrap();
 
-    Model::default()
+    // How often to pick each kind of tile, like `rounded:3,dots:1`
+    let mut registry = Registry::default();
+    if let Ok(weights) = env::var("TILES") {
+        registry.weigh(&weights);
+    }
+    let mut model = Model::new(registry);
+    // Tiles, paths or length. Press L to switch
+    model.coloring = env::var("COLORING")
+        .ok()
+        .and_then(|name| Coloring::parse(&name))
+        .unwrap_or(Coloring::Tiles);
+
+    // Follow a picture, like `example.png` from the assets folder, or any other path
+    if let Ok(name) = env::var("IMAGE") {
+        let mut path = PathBuf::from(&name);
+        if !path.exists() {
+            path = app.assets_path().unwrap().join(&name);
+        }
+        match Picture::open(&path) {
+            Ok(picture) => model.picture = Some(picture),
+            Err(e) => println!("Error opening {:?}: {:?}", path, e),
+        }
+        // Black and white point, and gamma, like `0.1,0.9,1.5`
+        if let Ok(levels) = env::var("LEVELS") {
+            match Levels::parse(&levels) {
+                Some(levels) => model.levels = levels,
+                None => println!("Can't read the levels {:?}", levels),
+            }
+        }
+        // None, random, ordered or diffusion
+        model.dither = env::var("DITHER")
+            .ok()
+            .and_then(|name| Dither::parse(&name))
+            .unwrap_or(Dither::Diffusion);
+    }
+
+    // Fit the tiles together, with this seed. Press W for another one
+    if let Ok(seed) = env::var("WFC") {
+        let seed = match seed.is_empty() {
+            true => random::<u32>().to_string(),
+            false => seed,
+        };
+        println!("Seed {}", seed);
+        model.wfc = Some(seed);
+    }
+    model.follow_picture(app.window_rect());
+    model.fill(app.window_rect());
+
+    // How R turns the tiles, press O and P for another easing and spread. The same seed
+    // spreads the turns the same way, even without one
+    model.transition = Transition::from_env(&env::var("SEED").unwrap_or_default());
+    if env::var("RECORD").is_ok() {
+        model.recorder = Some(Record::new(app));
+    }
+
+    // Split tiles into four with these chances, one for each level, like `0.6,0.4`. The
+    // quadtree fills the window as it is, so there is no dragging or zooming then
+    if let Ok(chances) = env::var("QUADTREE") {
+        let chances = chances
+            .split(',')
+            .filter_map(|chance| match chance.trim().parse::<f32>() {
+                Ok(chance) => Some(chance),
+                Err(_) => {
+                    println!("Can't read the chance {:?}", chance);
+                    None
+                }
+            })
+            .collect();
+        // How often a tile can be split
+        let depth = env::var("DEPTH")
+            .ok()
+            .and_then(|depth| depth.parse().ok())
+            .unwrap_or(3);
+        // Split by a noise field of this scale instead of at random, like `0.004`
+        let noise = env::var("NOISE").ok().and_then(|scale| scale.parse().ok());
+        let seed = env::var("SEED").unwrap_or_else(|_| random::<u32>().to_string());
+        println!("Seed {}", seed);
+
+        let subdivision = Subdivision {
+            chances,
+            depth,
+            noise,
+            seed,
+        };
+        let window = app.window_rect();
+        model.quadtree = Some(Quadtree::new(
+            subdivision,
+            window.w(),
+            window.h(),
+            200.,
+            &model.registry,
+        ));
+    }
+    model
 }
 
 fn event(app: &App, model: &mut Model, event: Event) {
@@ -61,173 +169,189 @@ fn event(app: &App, model: &mut Model, event: Event) {
 
             if let Some(KeyPressed(Key::Space)) = simple {
                 do_resize(model);
+                model.filled = None;
+                model.follow_picture(app.window_rect());
             }
 
+            // Turn the tiles, spreading from the mouse
             if let Some(KeyPressed(Key::R)) = simple {
-                model.tiles.iter_mut().for_each(|t| {
-                    t.orientation = random_range(0, 2);
-                });
+                model.turn_tiles(app.mouse.position(), app.window_rect());
+            }
+
+            if let Some(KeyPressed(Key::O)) = simple {
+                model.transition.easing = model.transition.easing.next();
+                println!("Easing {:?}", model.transition.easing);
+            }
+
+            if let Some(KeyPressed(Key::P)) = simple {
+                model.transition.spread = model.transition.spread.next();
+                println!("Spread {:?}", model.transition.spread);
+            }
+
+            // New tiles from the registry
+            if let Some(KeyPressed(Key::T)) = simple {
+                // Split the same way, with new tiles
+                if let Some(quadtree) = &mut model.quadtree {
+                    quadtree.draw += 1;
+                    quadtree.layout(quadtree.tile_size, &model.registry);
+                }
+                if model.wfc.is_some() {
+                    model.wfc = Some(random::<u32>().to_string());
+                }
+                model.tiles.clear();
+                model.filled = None;
+                model.follow_picture(app.window_rect());
+            }
+
+            // Fit the tiles together, with a new seed
+            if let Some(KeyPressed(Key::W)) = simple {
+                let seed = random::<u32>().to_string();
+                println!("Seed {}", seed);
+                model.wfc = Some(seed);
+                model.tiles.clear();
+                model.filled = None;
+                model.follow_picture(app.window_rect());
+            }
+
+            // Drag to look around, scroll to zoom in and out. The tiles that come into view are
+            // made in update, once a frame. Not with a quadtree, that fills the window as it is
+            let camera = model.quadtree.is_none();
+            if let Some(MousePressed(MouseButton::Left)) = simple {
+                if camera {
+                    model.camera.drag = Some(app.mouse.position());
+                }
+            }
+            if let Some(MouseReleased(MouseButton::Left)) = simple {
+                model.camera.drag = None;
+            }
+            if let Some(MouseMoved(position)) = simple {
+                if let Some(from) = model.camera.drag {
+                    model.camera.pan(position - from);
+                    model.camera.drag = Some(position);
+                }
+            }
+            if let Some(MouseWheel(delta, _)) = simple {
+                if camera {
+                    let lines = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.,
+                    };
+                    let least = model.least_zoom(app.window_rect());
+                    model
+                        .camera
+                        .zoom_at(ZOOM_STEP.powf(lines), app.mouse.position(), least);
+                }
+            }
+
+            if let Some(KeyPressed(Key::C)) = simple {
+                model.show_connections = !model.show_connections;
+            }
+
+            if let Some(KeyPressed(Key::L)) = simple {
+                model.coloring = model.coloring.next();
+            }
+
+            if let Some(KeyPressed(Key::E)) = simple {
+                match model.export_paths(&app.exe_name().unwrap(), app.window_rect()) {
+                    Ok(file_name) => println!("Saved to file://{}", file_name),
+                    Err(e) => println!("Error exporting paths: {:?}", e),
+                }
+            }
+
+            if let Some(KeyPressed(Key::Escape)) | Some(Closed) = simple {
+                if let Some(recorder) = &model.recorder {
+                    recorder.finish();
+                }
             }
         }
         _ => (),
     }
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
+fn update(app: &App, model: &mut Model, _update: Update) {
+    match &mut model.quadtree {
+        Some(quadtree) => quadtree.cover(app.window_rect(), &model.registry),
+        None => model.fill(app.window_rect()),
+    }
     model.update();
+
+    // Every frame while tiles turn, and all along while recording, to keep the video going
+    let busy = model.turning() || model.recorder.is_some();
+    match (busy, app.loop_mode()) {
+        (true, LoopMode::Wait) => app.set_loop_mode(LoopMode::RefreshSync),
+        (false, LoopMode::RefreshSync) => app.set_loop_mode(LoopMode::Wait),
+        _ => (),
+    }
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
     let draw = app.draw();
     model.view(app, &draw);
     draw.to_frame(app, &frame).unwrap();
+
+    if let Some(recorder) = &model.recorder {
+        recorder.record(app);
+    }
 }
 
 fn do_resize(model: &mut Model) {
     let sizes = vec![800., 400., 200., 100., 50., 25.];
 
-    let current_size = model.tiles[0].tile_size;
+    if let Some(quadtree) = &mut model.quadtree {
+        let next_size = sizes
+            .iter()
+            .find(|&&s| s < quadtree.tile_size)
+            .copied()
+            .unwrap_or(sizes[0]);
+        quadtree.layout(next_size, &model.registry);
+        return;
+    }
+
+    let current_size = model.tile_size;
     let next_size = sizes
         .iter()
         .find(|&&s| s < current_size)
         .unwrap_or(&sizes.first().unwrap());
 
-    model.tiles.iter_mut().for_each(|t| {
+    // The same tiles, smaller, with the one in the middle of the window still there
+    model.tile_size = *next_size;
+    model.camera.center *= *next_size / current_size;
+    model.tiles.values_mut().for_each(|t| {
         t.tile_size = *next_size;
     });
 }
 
-impl Tile {
-    fn n_instances(n: usize) -> Vec<Self> {
-        (0..n)
-            .map(|_| {
-                let orientation = random_range(0, 2);
-                let tile = Tile::new(orientation);
-
-                let chance = random_range(0., 100.);
-                match chance {
-                    0.0..=66.0 => {
-                        let mut tile = tile;
-                        tile.tile_type = TileType::Rounded;
-                        tile
-                    }
-                    66.0..=77.0 => {
-                        let mut tile = tile;
-                        tile.tile_type = TileType::Chamfered;
-                        tile
-                    }
-                    _ => {
-                        let mut tile = tile;
-                        tile.tile_type = TileType::Cross;
-                        tile
-                    }
-                }
-            })
-            .collect()
-    }
-
-    fn halve_circles(tile_size: f32, resolution: usize) -> Vec<Vec<Point2>> {
-        let half_tile: f32 = tile_size / 2.;
-
-        let bottom_left = pt2(-half_tile, -half_tile);
-        let top_right = pt2(half_tile, half_tile);
-
-        let radius = tile_size / 2.;
-        let start_angle = deg_to_rad(0.);
-        let end_angle = deg_to_rad(90.);
-
-        let bottom_left_points = (0..=resolution)
-            .map(|i| {
-                let t = map_range(i, 0, resolution, start_angle, end_angle);
-                let x = bottom_left.x + t.cos() * radius;
-                let y = bottom_left.y + t.sin() * radius;
-
-                pt2(x, y)
-            })
-            .collect();
-
-        let start_angle = deg_to_rad(180.);
-        let end_angle = deg_to_rad(270.);
-
-        let top_right_points = (0..=resolution)
-            .map(|i| {
-                let t = map_range(i, 0, resolution, start_angle, end_angle);
-                let x = top_right.x + t.cos() * radius;
-                let y = top_right.y + t.sin() * radius;
-
-                pt2(x, y)
-            })
-            .collect();
-
-        vec![bottom_left_points, top_right_points]
-    }
-
-    fn chamfered(tile_size: f32, _resolution: usize) -> Vec<Vec<Point2>> {
-        let half_tile: f32 = tile_size / 2.;
-
-        let bottom_left_points = vec![
-            pt2(0., -half_tile),
-            pt2(0., -half_tile / 2.),
-            pt2(-half_tile / 2., 0.),
-            pt2(-half_tile, 0.),
-        ];
-        let top_right_points = vec![
-            pt2(half_tile, 0.),
-            pt2(half_tile / 2., 0.),
-            pt2(0., half_tile / 2.),
-            pt2(0., half_tile),
-        ];
-
-        vec![bottom_left_points, top_right_points]
-    }
-
-    fn cross(tile_size: f32, _resolution: usize) -> Vec<Vec<nannou::prelude::Vec2>> {
-        let half_tile: f32 = tile_size / 2.;
-        let mut points = vec![];
-        points.push(vec![pt2(0., half_tile), pt2(0., -half_tile)]);
-        points.push(vec![pt2(half_tile, 0.), pt2(-half_tile, 0.)]);
-        points
-    }
-}
-
 impl Nannou for Model {
     fn view(&self, app: &App, draw: &Draw) {
-        let tile_size = self
-            .tiles
-            .iter()
-            .map(|t| t.tile_size as usize)
-            .max()
-            .unwrap() as f32;
-
-        // Move to top-left
-        let top_left = app.window_rect().top_left();
-        let draw = draw.xy(top_left - vec2(tile_size / 2., -tile_size / 2.));
+        if let Some(quadtree) = &self.quadtree {
+            self.view_quadtree(app, draw, quadtree);
+            return;
+        }
 
         draw.background().color(self.background_color);
-        // Add 2 to make sure we cover the whole window
-        let row_size = (app.window_rect().w() / tile_size) + 2.;
-        // Add 1 to make sure we cover the whole window
-        let max_rows = (app.window_rect().h() / tile_size) + 1.;
-
-        self.tiles
-            .chunks(row_size as usize)
-            .enumerate()
-            .for_each(|(i, row)| {
-                if i > max_rows as usize {
-                    return;
-                }
-                // Move the row down one tile
-                let draw = draw.y(i as f32 * -tile_size);
-                row.iter().enumerate().for_each(|(j, tile)| {
-                    // Move the tile to the right
-                    let draw = draw.x(j as f32 * tile_size);
-                    tile.view(app, &draw);
+        let window = app.window_rect();
+        let draw = &self.camera.transform(draw);
+        if self.coloring != Coloring::Tiles {
+            self.view_paths(draw, window);
+            return;
+        }
+
+        let (_, placed) = self.placed(window);
+        placed.into_iter().for_each(|(position, tile)| {
+            let draw = draw.xy(position);
+            tile.view(app, &draw);
+
+            if self.show_connections {
+                tile.connections().iter().flatten().for_each(|point| {
+                    draw.ellipse().xy(*point).radius(4.).color(WHITE);
                 });
-            });
+            }
+        });
     }
 
     fn update(&mut self) {
-        self.tiles.iter_mut().for_each(|t| t.update());
+        self.ease_turns();
+        self.tiles.values_mut().for_each(|t| t.update());
     }
 }
 
@@ -235,20 +359,15 @@ impl Nannou for Tile {
     fn view(&self, _app: &App, draw: &Draw) {
         draw.rect().w_h(self.tile_size, self.tile_size).no_fill().stroke_weight(1.0).stroke_color(BLACK);
         // Rotate around the center of the tile
-        let draw = draw.rotate(deg_to_rad(self.orientation as f32 * 90.));
-        let lines = match self.tile_type {
-            TileType::Rounded => Self::halve_circles(self.tile_size, self.resolution),
-            TileType::Chamfered => Self::chamfered(self.tile_size, self.resolution),
-            TileType::Cross => Self::cross(self.tile_size, self.resolution),
-        };
-
-        let weight = self.tile_size * LINE_FACTOR;
-        lines.into_iter().for_each(|points| {
-            draw.polyline()
-                .weight(weight)
-                .points(points.iter().cloned())
-                .color(self.line_color);
-        });
+        let draw = draw.rotate(deg_to_rad(self.angle * 90.));
+        let weight = self.tile_size * self.line_weight;
+        self.family.view(
+            &draw,
+            self.tile_size,
+            self.resolution,
+            weight,
+            self.line_color,
+        );
     }
 
     fn update(&mut self) {}
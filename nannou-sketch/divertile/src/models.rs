@@ -1,32 +1,123 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+use std::rc::Rc;
+
 use nannou::color::Hsla;
+use nannou::prelude::*;
+use nannou::rand::Rng;
+
+use bertools::picture::{Dither, Levels, Picture};
+use bertools::tween::{nearest, Transition, Tween};
+use bertools::Record;
+
+use crate::camera::Camera;
+use crate::paths::Coloring;
+use crate::quadtree::Quadtree;
+use crate::tiles::{Registry, TileFamily};
 
 pub struct Model {
     pub background_color: Hsla,
-    pub tiles: Vec<Tile>,
+    /// By column and row, going right and down. Made when the camera first sees them.
+    pub tiles: HashMap<(i64, i64), Tile>,
+    pub tile_size: f32,
+    pub camera: Camera,
+    /// The columns and rows there were tiles made for last, to only make those that came
+    /// into view since. None to make them all over again.
+    pub filled: Option<(Range<i64>, Range<i64>)>,
+    pub registry: Registry,
+    /// Mark where the lines of the tiles meet the edges.
+    pub show_connections: bool,
+    /// Tiles of several sizes, instead of `tiles`.
+    pub quadtree: Option<Quadtree>,
+    pub coloring: Coloring,
+    /// Picks the tiles, their colour and how they are turned, instead of chance.
+    pub picture: Option<Picture>,
+    pub levels: Levels,
+    pub dither: Dither,
+    /// Seed to fit the tiles together with, instead of picking them at random.
+    pub wfc: Option<String>,
+    /// Frames since the start, to time the turning tiles by.
+    pub frame: u64,
+    pub transition: Transition,
+    pub recorder: Option<Record>,
 }
 
 #[derive(Debug)]
 pub struct Tile {
     pub line_color: Hsla,
     pub orientation: u8,
+    /// How far it is turned right now, in quarter turns, on its way to `orientation`.
+    pub angle: f32,
+    pub turning: Option<Tween>,
+    /// Of the lines, relative to the size of the tile.
+    pub line_weight: f32,
     pub resolution: usize,
     pub tile_size: f32,
-    pub tile_type: TileType,
-}
-
-#[derive(Debug)]
-pub enum TileType {
-    Chamfered,
-    Rounded,
-    Cross,
+    pub family: Rc<dyn TileFamily>,
 }
 
 impl Tile {
-    pub fn new(orientation: u8) -> Self {
+    /// Turned by `rng`, so a seeded one makes the same tile every time.
+    pub fn new<R: Rng>(family: Rc<dyn TileFamily>, rng: &mut R) -> Self {
         let default = Self::default();
+        let mut tile = Self { family, ..default };
+        tile.turn(rng);
+        tile
+    }
+
+    /// Turn to any of the rotations of its family.
+    pub fn turn<R: Rng>(&mut self, rng: &mut R) {
+        let rotations = self.family.rotations();
+        self.orientation = rotations[rng.gen_range(0..rotations.len())];
+    }
+
+    /// Turn to the rotation the transition picks for `key`, bit by bit from frame `start`
+    /// on, the short way round.
+    pub fn turn_over<K: Hash>(&mut self, transition: &Transition, key: K, start: u64) {
+        let from = self.angle;
+        let rotations = self.family.rotations();
+        self.orientation = rotations[transition.pick(key, rotations.len())];
+        let to = nearest(from, self.orientation as f32, 4.);
+        self.turning = Some(transition.tween(from, to, start));
+    }
+
+    /// Where the lines of the tile meet its edges, turned like the tile, see
+    /// `TileFamily::connections`.
+    pub fn connections(&self) -> Vec<Vec<Point2>> {
+        let angle = deg_to_rad(self.orientation as f32 * 90.);
+        self.family
+            .connections()
+            .into_iter()
+            .map(|points| {
+                points
+                    .into_iter()
+                    .map(|point| point.rotate(angle) * self.tile_size)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+impl Model {
+    pub fn new(registry: Registry) -> Self {
         Self {
-            orientation,
-            ..default
+            background_color: hsla(0., 0., 0.04, 1.0),
+            tiles: HashMap::new(),
+            tile_size: Tile::default().tile_size,
+            camera: Camera::default(),
+            filled: None,
+            registry,
+            show_connections: false,
+            quadtree: None,
+            coloring: Coloring::Tiles,
+            picture: None,
+            levels: Levels::default(),
+            dither: Dither::Diffusion,
+            wfc: None,
+            frame: 0,
+            transition: Transition::new(""),
+            recorder: None,
         }
     }
 }
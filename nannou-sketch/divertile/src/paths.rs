@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bertools::saves_location;
+use bertools::schemes;
+use nannou::color::Mix;
+use nannou::prelude::*;
+
+use crate::models::{Model, Tile};
+use crate::LINE_FACTOR;
+
+/// Ends of lines closer than this, in pixels, are joined.
+const TOLERANCE: f32 = 0.01;
+
+/// How the lines are coloured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coloring {
+    /// Each tile on its own, in the colour of the tile.
+    Tiles,
+    /// Every path through the tiles in a colour of its own, from a palette.
+    Paths,
+    /// Paths from short to long, from cool to warm.
+    Length,
+}
+
+impl Coloring {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "tiles" => Some(Coloring::Tiles),
+            "paths" => Some(Coloring::Paths),
+            "length" => Some(Coloring::Length),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Coloring::Tiles => Coloring::Paths,
+            Coloring::Paths => Coloring::Length,
+            Coloring::Length => Coloring::Tiles,
+        }
+    }
+}
+
+/// A line through any number of tiles, stitched together from the lines in each of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    pub points: Vec<Point2>,
+    /// Ends where it started, a loop.
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|pair| pair[0].distance(pair[1]))
+            .sum()
+    }
+}
+
+/// Where the end of a line is, rounded so the ends of lines in tiles next to each other
+/// are the same.
+fn key(point: Point2) -> (i64, i64) {
+    (
+        (point.x / TOLERANCE).round() as i64,
+        (point.y / TOLERANCE).round() as i64,
+    )
+}
+
+impl Model {
+    /// The lines of all tiles in `window`, stitched together where they meet at the edges
+    /// of the tiles. Paths that run off the window or end in a tile come first, then loops.
+    pub fn paths(&self, window: Rect) -> Vec<Path> {
+        let (_, placed) = self.placed(window);
+        let lines: Vec<Vec<Point2>> = placed
+            .iter()
+            .flat_map(|(position, tile)| {
+                let angle = deg_to_rad(tile.orientation as f32 * 90.);
+                tile.family
+                    .lines(tile.tile_size, tile.resolution)
+                    .into_iter()
+                    .filter(|line| line.len() > 1)
+                    .map(move |line| {
+                        line.into_iter()
+                            .map(|point| point.rotate(angle) + *position)
+                            .collect()
+                    })
+            })
+            .collect();
+
+        // Which ends of which lines are at each place, 0 for the first end, 1 for the last
+        let mut ends: HashMap<(i64, i64), Vec<(usize, usize)>> = HashMap::new();
+        for (i, line) in lines.iter().enumerate() {
+            ends.entry(key(line[0])).or_default().push((i, 0));
+            ends.entry(key(line[line.len() - 1]))
+                .or_default()
+                .push((i, 1));
+        }
+        let loose = |(i, end): (usize, usize)| {
+            let point = match end {
+                0 => lines[i][0],
+                _ => lines[i][lines[i].len() - 1],
+            };
+            ends[&key(point)].len() == 1
+        };
+
+        let mut visited = vec![false; lines.len()];
+        let mut paths = vec![];
+        // Start at a loose end when there is one, so the whole path is found in one go
+        let starts: Vec<(usize, usize)> = (0..lines.len())
+            .flat_map(|i| [(i, 0), (i, 1)])
+            .filter(|start| loose(*start))
+            .chain((0..lines.len()).map(|i| (i, 0)))
+            .collect();
+        for (first, end) in starts {
+            if visited[first] {
+                continue;
+            }
+            let (mut line, mut end) = (first, end);
+            let mut points: Vec<Point2> = vec![];
+            let closed = loop {
+                visited[line] = true;
+                let mut stretch = lines[line].clone();
+                if end == 1 {
+                    stretch.reverse();
+                }
+                let skip = match points.is_empty() {
+                    true => 0,
+                    false => 1,
+                };
+                points.extend(stretch.into_iter().skip(skip));
+
+                let last = key(*points.last().unwrap());
+                if last == key(points[0]) && points.len() > 2 {
+                    break true;
+                }
+                match ends[&last].iter().find(|(other, _)| !visited[*other]) {
+                    Some(next) => (line, end) = *next,
+                    None => break false,
+                }
+            };
+            paths.push(Path { points, closed });
+        }
+        paths
+    }
+
+    /// A colour for each of `paths`, by `coloring`.
+    pub fn path_colors(&self, paths: &[Path]) -> Vec<Hsla> {
+        match self.coloring {
+            Coloring::Tiles => paths.iter().map(|_| Tile::default().line_color).collect(),
+            Coloring::Paths => {
+                let palette = schemes::SCHEME_VALENTINE.to_vec();
+                (0..paths.len())
+                    .map(|i| palette[i % palette.len()])
+                    .collect()
+            }
+            Coloring::Length => {
+                // By rank, so a few very long paths don't leave the rest all the same
+                let mut order: Vec<usize> = (0..paths.len()).collect();
+                order.sort_by(|a, b| paths[*a].length().total_cmp(&paths[*b].length()));
+                let mut colors = vec![*schemes::TIFFANY_BLUE; paths.len()];
+                for (rank, i) in order.into_iter().enumerate() {
+                    let factor = rank as f32 / (paths.len().max(2) - 1) as f32;
+                    colors[i] = schemes::TIFFANY_BLUE.mix(&schemes::AMARANTH, factor);
+                }
+                colors
+            }
+        }
+    }
+
+    /// The stitched paths in `window`, each in one colour.
+    pub fn view_paths(&self, draw: &Draw, window: Rect) {
+        let (tile_size, _) = self.placed(window);
+        let paths = self.paths(window);
+        for (path, color) in paths.iter().zip(self.path_colors(&paths)) {
+            draw.polyline()
+                .weight(tile_size * LINE_FACTOR)
+                .points(path.points.iter().cloned())
+                .color(color);
+        }
+    }
+
+    /// Write the stitched paths in `window` to the saves folder as SVG, each as one
+    /// polyline, as the camera sees them.
+    pub fn export_paths(&self, name: &str, window: Rect) -> io::Result<String> {
+        let (tile_size, _) = self.placed(window);
+        let paths = self.paths(window);
+        let color = |color: Hsla| {
+            let rgb: Rgb = color.into();
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                (rgb.red * 255.).round() as u8,
+                (rgb.green * 255.).round() as u8,
+                (rgb.blue * 255.).round() as u8
+            )
+        };
+
+        let mut svg = vec![
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+                w = window.w(),
+                h = window.h()
+            ),
+            format!(
+                r#"<rect width="100%" height="100%" fill="{}"/>"#,
+                color(self.background_color)
+            ),
+            format!(
+                r#"<g fill="none" stroke-width="{}">"#,
+                tile_size * LINE_FACTOR * self.camera.zoom
+            ),
+        ];
+        for (path, stroke) in paths.iter().zip(self.path_colors(&paths)) {
+            // SVG goes down from the top left, where the window goes up from the middle
+            let points: Vec<String> = path
+                .points
+                .iter()
+                .map(|point| {
+                    let point = self.camera.on_screen(*point) - window.top_left();
+                    format!("{:.2},{:.2}", point.x, -point.y)
+                })
+                .collect();
+            svg.push(format!(
+                r#"<polyline stroke="{}" points="{}"/>"#,
+                color(stroke),
+                points.join(" ")
+            ));
+        }
+        svg.push("</g>".to_string());
+        svg.push("</svg>".to_string());
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        let file_name = format!("{}{}-paths-{}.svg", saves_location(), name, now);
+        fs::write(&file_name, svg.join("\n") + "\n")?;
+        Ok(file_name)
+    }
+}
@@ -0,0 +1,73 @@
+use std::rc::Rc;
+
+use nannou::prelude::*;
+use nannou::rand::thread_rng;
+
+use crate::camera::Camera;
+use crate::models::Model;
+use crate::tiles::TileFamily;
+use crate::LINE_FACTOR;
+
+/// Below this, a cell has no clear edge to follow and its tile is turned at random.
+const EDGE: f32 = 0.2;
+
+impl Model {
+    /// Pick, turn, colour and weigh the tiles after the picture, when there is one. The
+    /// picture covers the tiles that are in `window` before the camera moves, wherever it is
+    /// now. Brighter cells get families that draw more, and thicker lines. Tiles are turned
+    /// so their lines run along the edges in the picture.
+    pub fn follow_picture(&mut self, window: Rect) {
+        let Some(picture) = &self.picture else {
+            return;
+        };
+        let (columns, rows) = self.region(&Camera::default(), window);
+        let cells: Vec<(i64, i64)> = rows
+            .clone()
+            .flat_map(|row| columns.clone().map(move |column| (column, row)))
+            .collect();
+        let (width, height) = (columns.count(), rows.count());
+        let samples = picture.sample(width, height, &self.levels);
+
+        // From the family that draws least to the one that draws most
+        let mut families: Vec<Rc<dyn TileFamily>> = self
+            .registry
+            .families
+            .iter()
+            .filter(|(_, weight)| *weight > 0.)
+            .map(|(family, _)| family.clone())
+            .collect();
+        families.sort_by(|a, b| a.ink().total_cmp(&b.ink()));
+        let brightness: Vec<f32> = samples.iter().map(|s| s.brightness).collect();
+        let choices = self.dither.choose(&brightness, width, families.len());
+
+        for ((cell, sample), choice) in cells.into_iter().zip(&samples).zip(choices) {
+            if !self.tiles.contains_key(&cell) {
+                let tile = self.new_tile();
+                self.tiles.insert(cell, tile);
+            }
+            let tile = self.tiles.get_mut(&cell).unwrap();
+            tile.family = families[choice].clone();
+            tile.line_color = sample.color;
+            tile.line_weight = LINE_FACTOR * (0.5 + sample.brightness);
+            tile.turning = None;
+
+            match tile.family.flow() {
+                Some(flow) if sample.edge > EDGE => {
+                    // How far the lines are off the edge, either way round
+                    let off = |rotation: u8| {
+                        let angle =
+                            (flow + rotation as f32 * PI / 2. - sample.direction).rem_euclid(PI);
+                        angle.min(PI - angle)
+                    };
+                    tile.orientation = tile
+                        .family
+                        .rotations()
+                        .into_iter()
+                        .min_by(|a, b| off(*a).total_cmp(&off(*b)))
+                        .unwrap();
+                }
+                _ => tile.turn(&mut thread_rng()),
+            }
+        }
+    }
+}
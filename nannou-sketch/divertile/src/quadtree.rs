@@ -0,0 +1,222 @@
+use std::hash::{Hash, Hasher};
+
+use nannou::noise::{self, NoiseFn, Seedable};
+use nannou::prelude::*;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::{Rng, SeedableRng};
+
+use crate::models::{Model, Tile};
+use crate::tiles::Registry;
+
+/// Width of the lines, and radius of the wings, relative to the tile.
+const WING: f32 = 1. / 3.;
+
+/// When to split a tile into four smaller ones.
+pub struct Subdivision {
+    /// Chance of splitting a tile, for each level. The last chance goes for the levels
+    /// after it.
+    pub chances: Vec<f32>,
+    /// How often a tile can be split, at most.
+    pub depth: usize,
+    /// Split where a noise field of this scale is below the chance, instead of at random.
+    pub noise: Option<f64>,
+    pub seed: String,
+}
+
+impl Subdivision {
+    fn chance(&self, level: usize) -> f32 {
+        self.chances
+            .get(level)
+            .or_else(|| self.chances.last())
+            .copied()
+            .unwrap_or(0.)
+    }
+}
+
+/// What the tiles of a layout are drawn from: whether to split them, which tiles go in the
+/// parts, and how they are turned.
+struct Draws<'a> {
+    splits: StdRng,
+    tiles: StdRng,
+    field: &'a noise::OpenSimplex,
+    registry: &'a Registry,
+}
+
+/// A tile in the quadtree that isn't split any further.
+#[derive(Debug)]
+pub struct Cell {
+    /// From the top left of the window.
+    pub center: Point2,
+    /// How often the tile it came from was split, 0 for a whole tile.
+    pub level: usize,
+    pub tile: Tile,
+}
+
+/// Tiles in a grid, some of them split into four, and those again, and so on. The colours
+/// swap with every level, and every tile has wings: circles in its background colour on
+/// its corners, that reach into the tiles next to it. That way the lines of a tile go on in
+/// the smaller tiles beside it. Drawn from the top left of the window, without the camera.
+pub struct Quadtree {
+    pub subdivision: Subdivision,
+    pub width: f32,
+    pub height: f32,
+    /// Of the tiles before they are split.
+    pub tile_size: f32,
+    /// Which of the sets of tiles the seed has to draw from, for new tiles split the same
+    /// way.
+    pub draw: u64,
+    pub cells: Vec<Cell>,
+}
+
+impl Quadtree {
+    /// Tiles of `tile_size` to cover `width` by `height`, split by `subdivision`.
+    pub fn new(
+        subdivision: Subdivision,
+        width: f32,
+        height: f32,
+        tile_size: f32,
+        registry: &Registry,
+    ) -> Self {
+        let mut quadtree = Self {
+            subdivision,
+            width,
+            height,
+            tile_size,
+            draw: 0,
+            cells: vec![],
+        };
+        quadtree.layout(tile_size, registry);
+        quadtree
+    }
+
+    /// Lay the tiles out again when the window changed size, so they cover all of it. The
+    /// tiles that were there already come out the same.
+    pub fn cover(&mut self, window: Rect, registry: &Registry) {
+        if (self.width, self.height) != (window.w(), window.h()) {
+            (self.width, self.height) = (window.w(), window.h());
+            self.layout(self.tile_size, registry);
+        }
+    }
+
+    /// Split the tiles again, at a different size. The same seed splits them the same way,
+    /// and draws the same tiles.
+    pub fn layout(&mut self, tile_size: f32, registry: &Registry) {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.subdivision.seed.hash(&mut hasher);
+        let seed = hasher.finish();
+        let field = noise::OpenSimplex::new().set_seed(seed as u32);
+
+        let columns = (self.width / tile_size).ceil() as usize;
+        let rows = (self.height / tile_size).ceil() as usize;
+        self.tile_size = tile_size;
+        self.cells = vec![];
+        for row in 0..rows {
+            for column in 0..columns {
+                // Every tile by its own, so it comes out the same however many there are
+                let rng = |draw: Option<u64>| {
+                    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                    (seed, column, row, draw).hash(&mut hasher);
+                    StdRng::seed_from_u64(hasher.finish())
+                };
+                let mut draws = Draws {
+                    splits: rng(None),
+                    tiles: rng(Some(self.draw)),
+                    field: &field,
+                    registry,
+                };
+                let center = pt2(column as f32 + 0.5, -(row as f32) - 0.5) * tile_size;
+                self.split(center, tile_size, 0, &mut draws);
+            }
+        }
+    }
+
+    fn split(&mut self, center: Point2, tile_size: f32, level: usize, draws: &mut Draws) {
+        let chance = self.subdivision.chance(level);
+        let split = level < self.subdivision.depth
+            && match self.subdivision.noise {
+                // Each level has its own slice of the field
+                Some(scale) => {
+                    let at = [
+                        center.x as f64 * scale,
+                        center.y as f64 * scale,
+                        level as f64,
+                    ];
+                    ((draws.field.get(at) as f32 + 1.) / 2.) < chance
+                }
+                None => draws.splits.gen_bool(chance.clamp(0., 1.) as f64),
+            };
+
+        if !split {
+            let mut tile = Tile::new(draws.registry.pick(&mut draws.tiles), &mut draws.tiles);
+            tile.tile_size = tile_size;
+            self.cells.push(Cell {
+                center,
+                level,
+                tile,
+            });
+            return;
+        }
+
+        let quarter = tile_size / 4.;
+        for offset in [
+            pt2(-quarter, quarter),
+            pt2(quarter, quarter),
+            pt2(-quarter, -quarter),
+            pt2(quarter, -quarter),
+        ] {
+            self.split(center + offset, tile_size / 2., level + 1, draws);
+        }
+    }
+}
+
+impl Model {
+    /// The background and line colour of a cell, swapped on every other level.
+    fn cell_colors(&self, cell: &Cell) -> (Hsla, Hsla) {
+        match cell.level % 2 {
+            0 => (self.background_color, cell.tile.line_color),
+            _ => (cell.tile.line_color, self.background_color),
+        }
+    }
+
+    pub fn view_quadtree(&self, app: &App, draw: &Draw, quadtree: &Quadtree) {
+        let draw = draw.xy(app.window_rect().top_left());
+        draw.background().color(self.background_color);
+
+        for cell in &quadtree.cells {
+            let (background, line) = self.cell_colors(cell);
+            let size = cell.tile.tile_size;
+            let draw = draw.xy(cell.center);
+            draw.rect().w_h(size, size).color(background);
+
+            let draw = draw.rotate(deg_to_rad(cell.tile.angle * 90.));
+            cell.tile
+                .family
+                .view(&draw, size, cell.tile.resolution, size * WING, line);
+        }
+
+        // Wings of larger tiles cover those of the smaller ones next to them
+        let deepest = quadtree
+            .cells
+            .iter()
+            .map(|cell| cell.level)
+            .max()
+            .unwrap_or(0);
+        for level in (0..=deepest).rev() {
+            for cell in quadtree.cells.iter().filter(|cell| cell.level == level) {
+                let (background, _) = self.cell_colors(cell);
+                let half = cell.tile.tile_size / 2.;
+                for corner in [
+                    pt2(-half, -half),
+                    pt2(half, -half),
+                    pt2(-half, half),
+                    pt2(half, half),
+                ] {
+                    draw.ellipse()
+                        .xy(cell.center + corner)
+                        .radius(cell.tile.tile_size * WING)
+                        .color(background);
+                }
+            }
+        }
+    }
+}
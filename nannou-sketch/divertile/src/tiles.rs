@@ -0,0 +1,439 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+use nannou::color::Hsla;
+use nannou::prelude::*;
+use nannou::rand::Rng;
+
+use crate::LINE_FACTOR;
+
+/// How close to the edge of a tile the end of a line must be to connect to the next tile.
+const EDGE: f32 = 0.001;
+/// How much the lines of a tile must run the same way to have a flow, from 0 to 1.
+const FLOW: f32 = 0.3;
+
+/// A kind of tile: what it draws, where its lines cross into the tiles next to it, and how
+/// it may be turned.
+pub trait TileFamily: fmt::Debug {
+    /// What it is called in `TILES`.
+    fn name(&self) -> &'static str;
+
+    /// The lines of an unturned tile of `tile_size`, around the origin.
+    fn lines(&self, tile_size: f32, resolution: usize) -> Vec<Vec<Point2>>;
+
+    /// Quarter turns, counter-clockwise, that the tile may be drawn in.
+    fn rotations(&self) -> Vec<u8>;
+
+    /// Where the lines of an unturned tile of size 1 meet its edge, one list for every
+    /// line. A line meets the edge at both ends, at one end when it stops in the tile, or
+    /// not at all.
+    fn connections(&self) -> Vec<Vec<Point2>> {
+        let on_edge = |point: &&Point2| 0.5 - point.x.abs().max(point.y.abs()) < EDGE;
+        self.lines(1.0, 8)
+            .iter()
+            .filter_map(|line| {
+                let ends: Vec<Point2> = [line.first(), line.last()]
+                    .into_iter()
+                    .flatten()
+                    .filter(on_edge)
+                    .copied()
+                    .collect();
+                match ends.is_empty() {
+                    true => None,
+                    false => Some(ends),
+                }
+            })
+            .collect()
+    }
+
+    /// What each side looks like, for `bertools::wfc`: right, up, left and down. Made of
+    /// where the lines meet the side, read counter-clockwise, so two sides fit when their
+    /// lines meet.
+    fn sockets(&self) -> [String; 4] {
+        let connections: Vec<Point2> = self.connections().into_iter().flatten().collect();
+        // How far along each side, counter-clockwise, a point on it is
+        let along: [fn(Point2) -> Option<f32>; 4] = [
+            |p| (0.5 - p.x < EDGE).then_some(p.y + 0.5),
+            |p| (0.5 - p.y < EDGE).then_some(0.5 - p.x),
+            |p| (0.5 + p.x < EDGE).then_some(0.5 - p.y),
+            |p| (0.5 + p.y < EDGE).then_some(p.x + 0.5),
+        ];
+        along.map(|along| {
+            let name = |positions: &mut Vec<f32>| {
+                positions.sort_by(|a, b| a.total_cmp(b));
+                positions
+                    .iter()
+                    .map(|t| format!("{:.2}", t))
+                    .collect::<Vec<String>>()
+                    .join("/")
+            };
+            let mut positions: Vec<f32> = connections.iter().filter_map(|p| along(*p)).collect();
+            let mut backwards: Vec<f32> = positions.iter().map(|t| 1. - t).collect();
+            let (forwards, backwards) = (name(&mut positions), name(&mut backwards));
+            match forwards.cmp(&backwards) {
+                Ordering::Equal => forwards,
+                Ordering::Less => forwards + "+",
+                Ordering::Greater => backwards + "-",
+            }
+        })
+    }
+
+    /// About how much of a tile of size 1 is drawn, with lines of `LINE_FACTOR`.
+    fn ink(&self) -> f32 {
+        self.lines(1.0, 16)
+            .iter()
+            .flat_map(|line| line.windows(2))
+            .map(|pair| pair[0].distance(pair[1]) * LINE_FACTOR)
+            .sum()
+    }
+
+    /// The angle the lines of an unturned tile mostly run at, in radians from 0 to PI. None
+    /// when they run every way about as much.
+    fn flow(&self) -> Option<f32> {
+        let segments: Vec<Vec2> = self
+            .lines(1.0, 16)
+            .iter()
+            .flat_map(|line| line.windows(2).map(|pair| pair[1] - pair[0]))
+            .collect();
+        // Add up the segments with their angles doubled, so opposite directions agree
+        let sum = segments.iter().fold(vec2(0., 0.), |sum, segment| {
+            let angle = 2. * segment.y.atan2(segment.x);
+            sum + vec2(angle.cos(), angle.sin()) * segment.length()
+        });
+        let length: f32 = segments.iter().map(|segment| segment.length()).sum();
+        match length > 0. && sum.length() / length > FLOW {
+            true => Some((sum.y.atan2(sum.x) / 2.).rem_euclid(PI)),
+            false => None,
+        }
+    }
+
+    /// Draw an unturned tile.
+    fn view(&self, draw: &Draw, tile_size: f32, resolution: usize, weight: f32, color: Hsla) {
+        self.lines(tile_size, resolution)
+            .into_iter()
+            .for_each(|points| {
+                draw.polyline().weight(weight).points(points).color(color);
+            });
+    }
+}
+
+/// Quarter circles around two opposite corners, the classic Smith tile.
+#[derive(Debug)]
+pub struct Rounded;
+
+/// Like `Rounded`, with straight lines cut off at the corners.
+#[derive(Debug)]
+pub struct Chamfered;
+
+/// Two straight lines crossing in the middle.
+#[derive(Debug)]
+pub struct Cross;
+
+/// One straight line from corner to corner.
+#[derive(Debug)]
+pub struct Diagonal;
+
+/// A dot in the middle, connected to nothing.
+#[derive(Debug)]
+pub struct Dots;
+
+/// A line from the edge that stops in the middle, in a dot.
+#[derive(Debug)]
+pub struct Ends;
+
+/// Half of the tile filled, corner to corner, the tile of Truchet himself.
+#[derive(Debug)]
+pub struct Wedge;
+
+/// Four `Rounded` tiles of half the size, turned to make a circle in the middle.
+#[derive(Debug)]
+pub struct MultiScale;
+
+impl TileFamily for Rounded {
+    fn name(&self) -> &'static str {
+        "rounded"
+    }
+
+    fn lines(&self, tile_size: f32, resolution: usize) -> Vec<Vec<Point2>> {
+        let half_tile: f32 = tile_size / 2.;
+        let radius = tile_size / 2.;
+
+        // Around the bottom left corner, and around the top right one
+        [
+            (pt2(-half_tile, -half_tile), 0.),
+            (pt2(half_tile, half_tile), 180.),
+        ]
+        .iter()
+        .map(|(corner, start)| {
+            (0..=resolution)
+                .map(|i| {
+                    let t = deg_to_rad(map_range(i, 0, resolution, *start, start + 90.));
+                    pt2(corner.x + t.cos() * radius, corner.y + t.sin() * radius)
+                })
+                .collect()
+        })
+        .collect()
+    }
+
+    fn rotations(&self) -> Vec<u8> {
+        vec![0, 1]
+    }
+}
+
+impl TileFamily for Chamfered {
+    fn name(&self) -> &'static str {
+        "chamfered"
+    }
+
+    fn lines(&self, tile_size: f32, _resolution: usize) -> Vec<Vec<Point2>> {
+        let half_tile: f32 = tile_size / 2.;
+
+        let bottom_left_points = vec![
+            pt2(0., -half_tile),
+            pt2(0., -half_tile / 2.),
+            pt2(-half_tile / 2., 0.),
+            pt2(-half_tile, 0.),
+        ];
+        let top_right_points = vec![
+            pt2(half_tile, 0.),
+            pt2(half_tile / 2., 0.),
+            pt2(0., half_tile / 2.),
+            pt2(0., half_tile),
+        ];
+
+        vec![bottom_left_points, top_right_points]
+    }
+
+    fn rotations(&self) -> Vec<u8> {
+        vec![0, 1]
+    }
+}
+
+impl TileFamily for Cross {
+    fn name(&self) -> &'static str {
+        "cross"
+    }
+
+    fn lines(&self, tile_size: f32, _resolution: usize) -> Vec<Vec<Point2>> {
+        let half_tile: f32 = tile_size / 2.;
+        vec![
+            vec![pt2(0., half_tile), pt2(0., -half_tile)],
+            vec![pt2(half_tile, 0.), pt2(-half_tile, 0.)],
+        ]
+    }
+
+    fn rotations(&self) -> Vec<u8> {
+        vec![0]
+    }
+}
+
+impl TileFamily for Diagonal {
+    fn name(&self) -> &'static str {
+        "diagonal"
+    }
+
+    fn lines(&self, tile_size: f32, _resolution: usize) -> Vec<Vec<Point2>> {
+        let half_tile: f32 = tile_size / 2.;
+        vec![vec![pt2(-half_tile, -half_tile), pt2(half_tile, half_tile)]]
+    }
+
+    fn rotations(&self) -> Vec<u8> {
+        vec![0, 1]
+    }
+}
+
+impl TileFamily for Dots {
+    fn name(&self) -> &'static str {
+        "dots"
+    }
+
+    fn lines(&self, _tile_size: f32, _resolution: usize) -> Vec<Vec<Point2>> {
+        vec![]
+    }
+
+    fn rotations(&self) -> Vec<u8> {
+        vec![0]
+    }
+
+    fn ink(&self) -> f32 {
+        PI * LINE_FACTOR * LINE_FACTOR
+    }
+
+    fn view(&self, draw: &Draw, _tile_size: f32, _resolution: usize, weight: f32, color: Hsla) {
+        draw.ellipse().radius(weight).color(color);
+    }
+}
+
+impl TileFamily for Ends {
+    fn name(&self) -> &'static str {
+        "ends"
+    }
+
+    fn lines(&self, tile_size: f32, _resolution: usize) -> Vec<Vec<Point2>> {
+        vec![vec![pt2(0., -tile_size / 2.), pt2(0., 0.)]]
+    }
+
+    fn rotations(&self) -> Vec<u8> {
+        vec![0, 1, 2, 3]
+    }
+
+    fn view(&self, draw: &Draw, tile_size: f32, resolution: usize, weight: f32, color: Hsla) {
+        self.lines(tile_size, resolution)
+            .into_iter()
+            .for_each(|points| {
+                draw.polyline().weight(weight).points(points).color(color);
+            });
+        draw.ellipse().radius(weight).color(color);
+    }
+}
+
+impl TileFamily for Wedge {
+    fn name(&self) -> &'static str {
+        "wedge"
+    }
+
+    fn lines(&self, _tile_size: f32, _resolution: usize) -> Vec<Vec<Point2>> {
+        vec![]
+    }
+
+    fn rotations(&self) -> Vec<u8> {
+        vec![0, 1, 2, 3]
+    }
+
+    fn ink(&self) -> f32 {
+        0.5
+    }
+
+    fn sockets(&self) -> [String; 4] {
+        // Filled against filled, so wedges make diamonds
+        ["clear", "clear", "filled", "filled"].map(|socket| socket.to_string())
+    }
+
+    fn flow(&self) -> Option<f32> {
+        // Along the long side of the half that is filled
+        Some(PI * 3. / 4.)
+    }
+
+    fn view(&self, draw: &Draw, tile_size: f32, _resolution: usize, _weight: f32, color: Hsla) {
+        let half_tile: f32 = tile_size / 2.;
+        draw.polygon()
+            .points([
+                pt2(-half_tile, -half_tile),
+                pt2(half_tile, -half_tile),
+                pt2(-half_tile, half_tile),
+            ])
+            .color(color);
+    }
+}
+
+impl TileFamily for MultiScale {
+    fn name(&self) -> &'static str {
+        "multiscale"
+    }
+
+    fn lines(&self, tile_size: f32, resolution: usize) -> Vec<Vec<Point2>> {
+        let quarter = tile_size / 4.;
+        // Each quarter turned so its arcs go around the middle of the whole tile
+        [
+            (pt2(-quarter, quarter), 1),
+            (pt2(quarter, quarter), 0),
+            (pt2(-quarter, -quarter), 0),
+            (pt2(quarter, -quarter), 1),
+        ]
+        .iter()
+        .flat_map(|(offset, orientation)| {
+            let angle = deg_to_rad(*orientation as f32 * 90.);
+            Rounded
+                .lines(tile_size / 2., resolution / 2)
+                .into_iter()
+                .map(move |line| {
+                    line.into_iter()
+                        .map(|point| point.rotate(angle) + *offset)
+                        .collect()
+                })
+        })
+        .collect()
+    }
+
+    fn rotations(&self) -> Vec<u8> {
+        vec![0]
+    }
+}
+
+/// Every kind of tile, and how often to pick it.
+pub struct Registry {
+    pub families: Vec<(Rc<dyn TileFamily>, f32)>,
+}
+
+impl Default for Registry {
+    /// Mostly rounded tiles, some chamfered ones and crosses, nothing else.
+    fn default() -> Self {
+        Self {
+            families: vec![
+                (Rc::new(Rounded), 66.),
+                (Rc::new(Chamfered), 11.),
+                (Rc::new(Cross), 23.),
+                (Rc::new(Diagonal), 0.),
+                (Rc::new(Dots), 0.),
+                (Rc::new(Ends), 0.),
+                (Rc::new(Wedge), 0.),
+                (Rc::new(MultiScale), 0.),
+            ],
+        }
+    }
+}
+
+impl Registry {
+    /// Weights like `rounded:3,dots:1`: only those families, each as often as its weight.
+    /// Keeps the weights it has when none of them can be read.
+    pub fn weigh(&mut self, weights: &str) {
+        let mut read = vec![];
+        for weight in weights.split(',').filter(|w| !w.trim().is_empty()) {
+            let parsed = weight.split_once(':').and_then(|(name, weight)| {
+                let index = self
+                    .families
+                    .iter()
+                    .position(|(family, _)| family.name() == name.trim())?;
+                Some((index, weight.trim().parse::<f32>().ok()?.max(0.)))
+            });
+            match parsed {
+                Some(parsed) => read.push(parsed),
+                None => println!("Can't read the tile weight {:?}", weight),
+            }
+        }
+        if read.iter().all(|(_, weight)| *weight == 0.) {
+            println!(
+                "No tiles to pick from in {:?}, keeping the default",
+                weights
+            );
+            return;
+        }
+
+        self.families
+            .iter_mut()
+            .for_each(|(_, weight)| *weight = 0.);
+        for (index, weight) in read {
+            self.families[index].1 = weight;
+        }
+    }
+
+    /// A family at random, by weight.
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> Rc<dyn TileFamily> {
+        let total: f32 = self.families.iter().map(|(_, weight)| weight).sum();
+        let mut chance = rng.gen_range(0. ..total);
+        for (family, weight) in &self.families {
+            if chance < *weight {
+                return family.clone();
+            }
+            chance -= weight;
+        }
+        // Only when rounding leaves a tiny bit of chance
+        self.families
+            .iter()
+            .rev()
+            .find(|(_, weight)| *weight > 0.)
+            .map(|(family, _)| family.clone())
+            .unwrap()
+    }
+}
@@ -0,0 +1,67 @@
+use nannou::prelude::*;
+
+use crate::models::Model;
+
+impl Model {
+    /// Turn every tile to any of the rotations of its family, not all at once but spreading
+    /// from `origin` on the screen, the way the transition spreads. Which rotation comes
+    /// from the seed of the transition, and how often the tiles were turned before.
+    pub fn turn_tiles(&mut self, origin: Point2, window: Rect) {
+        self.transition.changes += 1;
+        let frame = self.frame;
+        let area = self.camera.visible(window);
+        let from = self.camera.on_tiles(origin);
+        let delays: Vec<((i64, i64), u64)> = self
+            .tiles
+            .keys()
+            .map(|&(column, row)| {
+                let position = self.position(column, row);
+                ((column, row), self.transition.delay(position, from, area))
+            })
+            .collect();
+        for (cell, delay) in delays {
+            if let Some(tile) = self.tiles.get_mut(&cell) {
+                tile.turn_over(&self.transition, cell, frame + delay);
+            }
+        }
+
+        if let Some(quadtree) = &mut self.quadtree {
+            // Cells are placed from the top left of the window
+            let area = Rect::from_corners(pt2(0., 0.), pt2(window.w(), -window.h()));
+            let from = origin - window.top_left();
+            for (i, cell) in quadtree.cells.iter_mut().enumerate() {
+                let delay = self.transition.delay(cell.center, from, area);
+                cell.tile.turn_over(&self.transition, i, frame + delay);
+            }
+        }
+    }
+
+    /// Move the turning tiles on to the next frame, and stop those that got there.
+    pub fn ease_turns(&mut self) {
+        self.frame += 1;
+        let frame = self.frame;
+        let cells = self.quadtree.iter_mut().flat_map(|q| q.cells.iter_mut());
+        for tile in self
+            .tiles
+            .values_mut()
+            .chain(cells.map(|cell| &mut cell.tile))
+        {
+            tile.angle = match tile.turning {
+                Some(tween) => tween.value(frame),
+                None => tile.orientation as f32,
+            };
+            if tile.turning.is_some_and(|tween| tween.done(frame)) {
+                tile.turning = None;
+            }
+        }
+    }
+
+    /// Whether any tile is still on its way, or waiting to start.
+    pub fn turning(&self) -> bool {
+        let cells = self.quadtree.iter().flat_map(|q| q.cells.iter());
+        self.tiles
+            .values()
+            .chain(cells.map(|cell| &cell.tile))
+            .any(|tile| tile.turning.is_some())
+    }
+}
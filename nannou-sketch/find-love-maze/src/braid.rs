@@ -0,0 +1,106 @@
+use nannou::rand::seq::SliceRandom;
+use nannou::rand::Rng;
+
+use crate::models::{Layout, Model};
+use crate::playback::Step;
+
+impl Model {
+    pub fn dead_ends(&self) -> Vec<usize> {
+        (0..self.cell_count())
+            .filter(|idx| self.is_visited(*idx) && self.passages(*idx).len() == 1)
+            .collect()
+    }
+
+    /// Knock through the wall at the end of a `fraction` of the dead ends, which turns the
+    /// perfect maze into one with loops. Prefers knocking into another dead end, so a single
+    /// wall removes two of them.
+    pub fn braid(&mut self, fraction: f32) {
+        let mut dead_ends = self.dead_ends();
+        dead_ends.shuffle(&mut self.rng);
+
+        for idx in dead_ends {
+            // An earlier knock-through may already have fixed this one.
+            if self.passages(idx).len() != 1 || self.rng.gen::<f32>() >= fraction {
+                continue;
+            }
+
+            let walled: Vec<usize> = self
+                .neighbors(idx)
+                .into_iter()
+                .filter(|neighbor| {
+                    self.is_visited(*neighbor)
+                        && !self.is_open(idx, *neighbor)
+                        && !self.is_crossing(*neighbor)
+                })
+                .collect();
+            let best: Vec<usize> = walled
+                .iter()
+                .copied()
+                .filter(|neighbor| self.passages(*neighbor).len() == 1)
+                .collect();
+
+            let pick = if best.is_empty() { &walled } else { &best };
+            if let Some(neighbor) = pick.choose(&mut self.rng) {
+                self.apply(Step::Knock {
+                    from: idx,
+                    to: *neighbor,
+                });
+            }
+        }
+    }
+
+    /// Cull dead ends until a `fraction` of all cells is gone. Culled cells are no longer
+    /// part of the maze, so every pass uncovers new dead ends to remove.
+    pub fn sparsify(&mut self, fraction: f32) {
+        let mut to_cull = (self.cell_count() as f32 * fraction) as usize;
+
+        while to_cull > 0 {
+            let mut dead_ends: Vec<usize> = self
+                .dead_ends()
+                .into_iter()
+                .filter(|idx| {
+                    !self.is_start(*idx)
+                        && Some(*idx) != self.exit
+                        && (!self.weave || self.tunnels(*idx).is_empty())
+                })
+                .collect();
+            if dead_ends.is_empty() {
+                break;
+            }
+            dead_ends.shuffle(&mut self.rng);
+
+            for idx in dead_ends {
+                if to_cull == 0 {
+                    break;
+                }
+                // Culling its neighbor may have cut this one loose already.
+                if self.passages(idx).len() != 1 {
+                    continue;
+                }
+                self.apply(Step::Cull { cell: idx });
+                to_cull -= 1;
+            }
+        }
+    }
+
+    fn is_start(&self, idx: usize) -> bool {
+        match self.layout {
+            Layout::Square => self.cells[idx].start,
+            Layout::Polar => self.polar_cells[idx].start,
+        }
+    }
+
+    fn is_crossing(&self, idx: usize) -> bool {
+        match self.layout {
+            Layout::Square => self.cells[idx].crossing.is_some(),
+            Layout::Polar => false,
+        }
+    }
+
+    pub fn unvisit(&mut self, idx: usize) {
+        match self.layout {
+            Layout::Square => self.cells[idx].visited = false,
+            Layout::Polar => self.polar_cells[idx].visited = false,
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+
+use bertools::saves_location;
+use nannou::geom::{Point2, Vec3};
+use nannou::prelude::{pt2, vec3};
+
+use crate::hidden::contours;
+use crate::models::{Layout, Model};
+
+/// Physical sizes of a maze that gets laser cut or 3D printed, in millimetres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fabrication {
+    /// From the middle of one wall to the middle of the next.
+    pub cell_size: f32,
+    pub wall_thickness: f32,
+    /// How far the walls stand out above the base plate.
+    pub wall_height: f32,
+    pub base_thickness: f32,
+    /// Width of the laser cut. The cut lines are moved out by half of it, so the pieces come
+    /// out at the right size.
+    pub kerf: f32,
+}
+
+impl Default for Fabrication {
+    fn default() -> Self {
+        Self {
+            cell_size: 10.0,
+            wall_thickness: 2.0,
+            wall_height: 8.0,
+            base_thickness: 3.0,
+            kerf: 0.2,
+        }
+    }
+}
+
+impl Fabrication {
+    /// Sizes like `cell=10,wall=2,height=8,base=3,kerf=0.2`. Sizes that are left out keep
+    /// their default.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut fabrication = Self::default();
+        for size in spec.split(',') {
+            let (name, value) = size.split_once('=')?;
+            let value: f32 = value.trim().parse().ok()?;
+            match name.trim() {
+                "cell" => fabrication.cell_size = value,
+                "wall" => fabrication.wall_thickness = value,
+                "height" => fabrication.wall_height = value,
+                "base" => fabrication.base_thickness = value,
+                "kerf" => fabrication.kerf = value,
+                _ => return None,
+            }
+        }
+        Some(fabrication)
+    }
+
+    /// Where block boundary `b` is, in millimetres from the outside of the first wall. Even
+    /// blocks are as wide as a wall, odd ones fill the rest of a cell.
+    fn boundary(&self, b: i32) -> f32 {
+        (b / 2) as f32 * self.cell_size + (b % 2) as f32 * self.wall_thickness
+    }
+
+    fn millimetres(&self, corner: Point2) -> Point2 {
+        pt2(
+            self.boundary(corner.x as i32),
+            self.boundary(corner.y as i32),
+        )
+    }
+}
+
+impl Model {
+    /// The walls on a grid of blocks, two blocks per cell and one more for the last walls.
+    /// Even columns and rows are walls and the posts where they meet, odd ones the insides of
+    /// the cells. Cells that are not part of the maze are filled in.
+    fn wall_blocks(&self) -> HashSet<(i32, i32)> {
+        let mut blocks = HashSet::new();
+        for cell in &self.cells {
+            let (x, y) = (2 * cell.col + 1, 2 * cell.row + 1);
+            for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                if self.has_wall(cell, dx, dy) {
+                    blocks.insert((x + dx, y + dy));
+                    // The posts at both ends of the wall
+                    blocks.insert((x + dx + dy, y + dy + dx));
+                    blocks.insert((x + dx - dy, y + dy - dx));
+                }
+            }
+            if !cell.visited {
+                blocks.insert((x, y));
+            }
+        }
+        blocks
+    }
+
+    /// The outlines of the walls as closed polylines, for laser cutting. The walls are on one
+    /// layer and the base plate they are glued on on another. Crossings come out as plain
+    /// intersections.
+    pub fn dxf(&self, fabrication: &Fabrication) -> String {
+        // Group codes and values on alternating lines. Units 4 are millimetres
+        let codes = |text: &str| text.split(' ').map(String::from).collect::<Vec<String>>();
+        let mut lines = codes("0 SECTION 2 HEADER 9 $INSUNITS 70 4 0 ENDSEC 0 SECTION 2 ENTITIES");
+
+        let mut polyline = |layer: &str, outline: Vec<Point2>| {
+            lines.extend(codes(&format!(
+                "0 POLYLINE 8 {} 66 1 10 0 20 0 30 0 70 1",
+                layer
+            )));
+            for point in offset(&outline, fabrication.kerf / 2.0) {
+                lines.extend(codes(&format!(
+                    "0 VERTEX 8 {} 10 {:.3} 20 {:.3}",
+                    layer, point.x, point.y
+                )));
+            }
+            lines.extend(codes(&format!("0 SEQEND 8 {}", layer)));
+        };
+
+        for contour in contours(&self.wall_blocks()) {
+            let outline = contour
+                .into_iter()
+                .map(|c| fabrication.millimetres(c))
+                .collect();
+            polyline("WALLS", simplify(outline));
+        }
+
+        let width = fabrication.boundary(2 * self.cols + 1);
+        let height = fabrication.boundary(2 * self.rows + 1);
+        polyline(
+            "PLATE",
+            vec![
+                pt2(0.0, 0.0),
+                pt2(width, 0.0),
+                pt2(width, height),
+                pt2(0.0, height),
+            ],
+        );
+
+        lines.extend(codes("0 ENDSEC 0 EOF"));
+        lines.join("\n") + "\n"
+    }
+
+    /// A closed mesh of the walls standing on a base plate, for 3D printing.
+    pub fn stl(&self, fabrication: &Fabrication) -> String {
+        let blocks = self.wall_blocks();
+        let (cols, rows) = (2 * self.cols + 1, 2 * self.rows + 1);
+        let base = fabrication.base_thickness;
+        let top = base + fabrication.wall_height;
+
+        // Every face is cut along the block boundaries, so neighbouring faces share their
+        // corners and the mesh has no gaps
+        let mut facets = vec![];
+        for x in 0..cols {
+            for y in 0..rows {
+                let filled = blocks.contains(&(x, y));
+                let (x0, x1) = (fabrication.boundary(x), fabrication.boundary(x + 1));
+                let (y0, y1) = (fabrication.boundary(y), fabrication.boundary(y + 1));
+                let floor = |z: f32| {
+                    [
+                        vec3(x0, y0, z),
+                        vec3(x1, y0, z),
+                        vec3(x1, y1, z),
+                        vec3(x0, y1, z),
+                    ]
+                };
+
+                let height = if filled { top } else { base };
+                quad(&mut facets, floor(height), vec3(0.0, 0.0, 1.0));
+                quad(&mut facets, floor(0.0), vec3(0.0, 0.0, -1.0));
+
+                for (dx, dy) in [(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                    let (nx, ny) = (x + dx, y + dy);
+                    let outside = nx < 0 || ny < 0 || nx >= cols || ny >= rows;
+                    let side = |z0: f32, z1: f32| {
+                        let (a, b) = match (dx, dy) {
+                            (0, -1) => ((x0, y0), (x1, y0)),
+                            (1, 0) => ((x1, y0), (x1, y1)),
+                            (0, 1) => ((x1, y1), (x0, y1)),
+                            _ => ((x0, y1), (x0, y0)),
+                        };
+                        [
+                            vec3(a.0, a.1, z0),
+                            vec3(b.0, b.1, z0),
+                            vec3(b.0, b.1, z1),
+                            vec3(a.0, a.1, z1),
+                        ]
+                    };
+                    let normal = vec3(dx as f32, dy as f32, 0.0);
+
+                    if outside {
+                        quad(&mut facets, side(0.0, base), normal);
+                    }
+                    if filled && (outside || !blocks.contains(&(nx, ny))) {
+                        quad(&mut facets, side(base, top), normal);
+                    }
+                }
+            }
+        }
+
+        let mut stl = format!("solid maze-{}\n", self.seed);
+        for (normal, corners) in facets {
+            stl.push_str(&format!(
+                "facet normal {} {} {}\n  outer loop\n",
+                normal.x, normal.y, normal.z
+            ));
+            for corner in corners {
+                stl.push_str(&format!(
+                    "    vertex {:.3} {:.3} {:.3}\n",
+                    corner.x, corner.y, corner.z
+                ));
+            }
+            stl.push_str("  endloop\nendfacet\n");
+        }
+        stl.push_str(&format!("endsolid maze-{}\n", self.seed));
+        stl
+    }
+
+    /// Write a DXF and an STL file of the maze to the saves folder, named after the seed.
+    /// Only square mazes can be made.
+    pub fn fabricate(&self, name: &str, fabrication: &Fabrication) -> io::Result<Vec<String>> {
+        if self.layout != Layout::Square {
+            return Err(io::Error::other("only square mazes can be fabricated"));
+        }
+
+        let mut file_names = vec![];
+        for (extension, contents) in [
+            ("dxf", self.dxf(fabrication)),
+            ("stl", self.stl(fabrication)),
+        ] {
+            let file_name = format!("{}{}-{}.{}", saves_location(), name, self.seed, extension);
+            fs::write(&file_name, contents)?;
+            file_names.push(file_name);
+        }
+        Ok(file_names)
+    }
+}
+
+/// Add a rectangle as two triangles, turned so they face `normal`.
+fn quad(facets: &mut Vec<(Vec3, [Vec3; 3])>, corners: [Vec3; 4], normal: Vec3) {
+    for [a, b, c] in [
+        [corners[0], corners[1], corners[2]],
+        [corners[0], corners[2], corners[3]],
+    ] {
+        if (b - a).cross(c - a).dot(normal) < 0.0 {
+            facets.push((normal, [a, c, b]));
+        } else {
+            facets.push((normal, [a, b, c]));
+        }
+    }
+}
+
+/// Leave out the corners in the middle of a straight line.
+fn simplify(outline: Vec<Point2>) -> Vec<Point2> {
+    let count = outline.len();
+    (0..count)
+        .filter(|&i| {
+            let previous = outline[(i + count - 1) % count];
+            let next = outline[(i + 1) % count];
+            (outline[i] - previous).perp_dot(next - outline[i]).abs() > f32::EPSILON
+        })
+        .map(|i| outline[i])
+        .collect()
+}
+
+/// Move every side of a closed outline out by `distance`, keeping the corners sharp. The
+/// outline goes counter-clockwise around what it encloses, so outward is to the right.
+fn offset(outline: &[Point2], distance: f32) -> Vec<Point2> {
+    let count = outline.len();
+    let normal = |from: Point2, to: Point2| {
+        let along = (to - from).normalize();
+        pt2(along.y, -along.x)
+    };
+    (0..count)
+        .map(|i| {
+            let previous = outline[(i + count - 1) % count];
+            let point = outline[i];
+            let next = outline[(i + 1) % count];
+            let (a, b) = (normal(previous, point), normal(point, next));
+            point + (a + b) / (1.0 + a.dot(b)) * distance
+        })
+        .collect()
+}
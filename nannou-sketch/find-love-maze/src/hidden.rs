@@ -0,0 +1,468 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use nannou::geom::Point2;
+use nannou::prelude::pt2;
+use nannou::rand::seq::SliceRandom;
+
+use crate::icons::Shape;
+use crate::models::{Layout, Model};
+
+/// Cells kept free between the message and the border of the maze.
+const MARGIN: i32 = 2;
+
+/// A five by seven pixel font, top row first.
+#[rustfmt::skip]
+const FONT: [(char, [&str; 7]); 36] = [
+    ('A', [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('B', ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."]),
+    ('C', [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."]),
+    ('D', ["####.", "#...#", "#...#", "#...#", "#...#", "#...#", "####."]),
+    ('E', ["#####", "#....", "#....", "####.", "#....", "#....", "#####"]),
+    ('F', ["#####", "#....", "#....", "####.", "#....", "#....", "#...."]),
+    ('G', [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".###."]),
+    ('H', ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('I', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "#####"]),
+    ('J', ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "#....", "#####"]),
+    ('M', ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"]),
+    ('N', ["#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#", "#...#"]),
+    ('O', [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('P', ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."]),
+    ('Q', [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"]),
+    ('R', ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"]),
+    ('S', [".####", "#....", "#....", ".###.", "....#", "....#", "####."]),
+    ('T', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('U', ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('V', ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('W', ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."]),
+    ('X', ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."]),
+    ('Z', ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"]),
+    ('0', [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."]),
+    ('1', ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('2', [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"]),
+    ('3', ["####.", "....#", "....#", ".###.", "....#", "....#", "####."]),
+    ('4', ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."]),
+    ('5', ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."]),
+    ('6', [".###.", "#....", "#....", "####.", "#...#", "#...#", ".###."]),
+    ('7', ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]),
+    ('8', [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."]),
+    ('9', [".###.", "#...#", "#...#", ".####", "....#", "....#", ".###."]),
+];
+
+/// Outlines of a message, in cells, fitted into a `cols` by `rows` maze. The message is one
+/// of the icon shapes, or else text, which gets one outline per character. Holes are left
+/// out, a corridor can't get in and out of them again.
+pub fn outlines(message: &str, cols: i32, rows: i32) -> Vec<Vec<Point2>> {
+    let (room_w, room_h) = ((cols - 2 * MARGIN) as f32, (rows - 2 * MARGIN) as f32);
+
+    let Some(shape) = Shape::parse(message) else {
+        return text_outlines(message, room_w, room_h)
+            .into_iter()
+            .map(|outline| {
+                outline
+                    .into_iter()
+                    .map(|point| point + pt2(MARGIN as f32, MARGIN as f32))
+                    .collect()
+            })
+            .collect();
+    };
+
+    let all = shape.outlines();
+    let outlines: Vec<Vec<Point2>> = all
+        .iter()
+        .enumerate()
+        .filter(|(i, outline)| {
+            !all.iter()
+                .enumerate()
+                .any(|(j, other)| *i != j && contains(other, outline[0]))
+        })
+        .map(|(_, outline)| outline.clone())
+        .collect();
+
+    // Stretch the shape as far as it goes, keeping its proportions
+    let points = outlines.iter().flatten();
+    let min = points
+        .clone()
+        .fold(pt2(f32::MAX, f32::MAX), |a, b| a.min(*b));
+    let max = points.fold(pt2(f32::MIN, f32::MIN), |a, b| a.max(*b));
+    let size = max - min;
+    let scale = (room_w / size.x).min(room_h / size.y);
+    let offset = pt2(cols as f32, rows as f32) / 2.0 - (min + size / 2.0) * scale;
+
+    outlines
+        .into_iter()
+        .map(|outline| outline.into_iter().map(|p| p * scale + offset).collect())
+        .collect()
+}
+
+/// Whether `point` lies inside the closed `outline`.
+fn contains(outline: &[Point2], point: Point2) -> bool {
+    let mut inside = false;
+    for (a, b) in outline.iter().zip(outline.iter().cycle().skip(1)) {
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Outer outlines of the characters of `text`, as big as fits in `width` by `height` and
+/// centered in it. Characters that aren't in the font are left out.
+fn text_outlines(text: &str, width: f32, height: f32) -> Vec<Vec<Point2>> {
+    let glyphs: Vec<&[&str; 7]> = text
+        .to_uppercase()
+        .chars()
+        .filter_map(|c| FONT.iter().find(|(glyph, _)| *glyph == c))
+        .map(|(_, pixels)| pixels)
+        .collect();
+    if glyphs.is_empty() {
+        return vec![];
+    }
+
+    // Every pixel becomes `scale` by `scale` cells, plus one to make the strokes bold.
+    // Characters are a pixel apart.
+    let count = glyphs.len() as i32;
+    let scale = (1..)
+        .take_while(|scale| {
+            let text_width = count * (5 * scale + 1) + (count - 1) * scale;
+            text_width as f32 <= width && (7 * scale + 1) as f32 <= height
+        })
+        .last()
+        .unwrap_or(1);
+    let text_width = count * (5 * scale + 1) + (count - 1) * scale;
+    let origin = pt2(
+        ((width - text_width as f32) / 2.0).floor(),
+        ((height - (7 * scale + 1) as f32) / 2.0).floor(),
+    );
+
+    glyphs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, pixels)| {
+            let mut mask = HashSet::new();
+            for (row, line) in pixels.iter().enumerate() {
+                for (col, pixel) in line.chars().enumerate() {
+                    if pixel != '#' {
+                        continue;
+                    }
+                    // The font goes top down, rows go up
+                    let (x, y) = (col as i32 * scale, (6 - row as i32) * scale);
+                    for dx in 0..=scale {
+                        for dy in 0..scale {
+                            mask.insert((x + dx, y + dy));
+                        }
+                    }
+                }
+            }
+
+            let left = i as i32 * (6 * scale + 1);
+            let outline = outer_contour(&mask)?;
+            Some(
+                outline
+                    .into_iter()
+                    .map(|point| origin + point + pt2(left as f32, 0.0))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// The longest closed outline around the cells in `mask`, along the corners of the cells.
+fn outer_contour(mask: &HashSet<(i32, i32)>) -> Option<Vec<Point2>> {
+    contours(mask)
+        .into_iter()
+        .max_by_key(|contour| contour.len())
+}
+
+/// Every closed outline around the cells in `mask`, along the corners of the cells. Outlines
+/// go counter-clockwise around the cells, so holes go clockwise.
+pub fn contours(mask: &HashSet<(i32, i32)>) -> Vec<Vec<Point2>> {
+    // Every side between a cell in the mask and one outside of it, counter-clockwise
+    let mut edges: HashMap<(i32, i32), Vec<(i32, i32)>> = HashMap::new();
+    for &(x, y) in mask {
+        let sides = [
+            ((0, -1), (x, y), (x + 1, y)),
+            ((1, 0), (x + 1, y), (x + 1, y + 1)),
+            ((0, 1), (x + 1, y + 1), (x, y + 1)),
+            ((-1, 0), (x, y + 1), (x, y)),
+        ];
+        for ((dx, dy), from, to) in sides {
+            if !mask.contains(&(x + dx, y + dy)) {
+                edges.entry(from).or_default().push(to);
+            }
+        }
+    }
+
+    let mut contours = vec![];
+    while let Some(&start) = edges.keys().min() {
+        let mut contour = vec![];
+        let mut corner = start;
+        while let Some(next) = edges.get_mut(&corner).and_then(|to| to.pop()) {
+            if edges.get(&corner).map(|to| to.is_empty()).unwrap_or(false) {
+                edges.remove(&corner);
+            }
+            contour.push(pt2(corner.0 as f32, corner.1 as f32));
+            corner = next;
+        }
+        contours.push(contour);
+    }
+    contours
+}
+
+/// The cells a straight line from `from` to `to` goes through, one step at a time and
+/// without cutting corners. `from` itself is left out.
+fn line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let distance = |(x, y): (i32, i32)| ((x - from.0) * dy - (y - from.1) * dx).abs();
+
+    let mut cells = vec![];
+    let mut cell = from;
+    while cell != to {
+        let horizontal = (cell.0 + dx.signum(), cell.1);
+        let vertical = (cell.0, cell.1 + dy.signum());
+        cell = if cell.0 == to.0 {
+            vertical
+        } else if cell.1 == to.1 || distance(horizontal) <= distance(vertical) {
+            horizontal
+        } else {
+            vertical
+        };
+        cells.push(cell);
+    }
+    cells
+}
+
+/// Cut out every loop of a walk, so no cell appears twice.
+fn erase_loops(walk: Vec<usize>) -> Vec<usize> {
+    let mut path: Vec<usize> = vec![];
+    for cell in walk {
+        match path.iter().position(|seen| *seen == cell) {
+            Some(seen) => path.truncate(seen + 1),
+            None => path.push(cell),
+        }
+    }
+    path
+}
+
+impl Model {
+    /// Every cell the outlines of the message go through, in order, one walk per outline.
+    pub fn message_walks(&self) -> Vec<Vec<usize>> {
+        let Some(message) = &self.message else {
+            return vec![];
+        };
+        if self.layout != Layout::Square {
+            return vec![];
+        }
+
+        let to_cell = |point: &Point2| {
+            (
+                (point.x.floor() as i32).clamp(0, self.cols - 1),
+                (point.y.floor() as i32).clamp(0, self.rows - 1),
+            )
+        };
+
+        let mut outlines = outlines(message, self.cols, self.rows);
+        outlines.sort_by(|a, b| {
+            let left = |outline: &Vec<Point2>| outline.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+            left(a).total_cmp(&left(b))
+        });
+
+        outlines
+            .iter()
+            .filter(|outline| !outline.is_empty())
+            .map(|outline| {
+                let corners: Vec<(i32, i32)> = outline.iter().map(to_cell).collect();
+                let mut walk = vec![corners[0]];
+                for corner in corners.iter().skip(1).chain([&corners[0]]) {
+                    walk.extend(line(*walk.last().unwrap(), *corner));
+                }
+                // Stop right before closing the outline, which would erase all of it
+                walk.pop();
+                walk.into_iter()
+                    .filter_map(|(col, row)| self.index(col, row))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Pick a cell for the start room that keeps the room clear of the message.
+    pub fn choose_message_start(&mut self) -> Option<usize> {
+        let message: HashSet<usize> = self.message_walks().into_iter().flatten().collect();
+        if message.is_empty() {
+            return None;
+        }
+
+        // The whole room has to be inside the maze and away from the message
+        let clear = |col: i32, row: i32| {
+            (-1..=1).all(|x| {
+                (-1..=1).all(|y| {
+                    self.index(col + x, row + y)
+                        .map(|idx| !message.contains(&idx))
+                        .unwrap_or(false)
+                })
+            })
+        };
+        let mut candidates: Vec<usize> = (0..self.cells.len())
+            .filter(|idx| clear(self.cells[*idx].col, self.cells[*idx].row))
+            .collect();
+
+        // Close to the center, but not always the same cell
+        let center = pt2(self.cols as f32, self.rows as f32) / 2.0;
+        candidates.sort_by(|a, b| {
+            let distance = |idx: &usize| self.cell_center(*idx).distance(center);
+            distance(a).total_cmp(&distance(b))
+        });
+        candidates.truncate(8);
+        candidates.choose(&mut self.rng).copied()
+    }
+
+    /// The corridor from the start room, along the message, to the border. Every cell in it
+    /// is carved before the rest of the maze grows around it, which makes it the solution.
+    /// Also picks the end of the corridor as the exit. Empty if there is no message, or if
+    /// any of it doesn't fit, rather than leave part of it out.
+    pub fn plan_message(&mut self) -> VecDeque<usize> {
+        let walks = self.message_walks();
+        let Some(from) = self.current.map(|current| current as usize) else {
+            return VecDeque::new();
+        };
+        if walks.is_empty() {
+            return VecDeque::new();
+        }
+
+        let room: HashSet<usize> = (0..self.cells.len())
+            .filter(|idx| self.cells[*idx].start)
+            .collect();
+
+        let mut walk = vec![from];
+        let mut traced = 0;
+        for (i, outline) in walks.iter().enumerate() {
+            // Keep clear of what is still to be traced
+            let ahead: HashSet<usize> = walks[i..].iter().flatten().copied().collect();
+            let arrived = |idx: usize| outline.contains(&idx);
+            // Leaving a part out would spell something else
+            let Some(connector) = self.connect(&mut walk, traced, &room, &ahead, arrived) else {
+                // Text has an outline for every character, shapes may have several
+                let letter = self
+                    .message
+                    .as_deref()
+                    .filter(|message| Shape::parse(message).is_none())
+                    .and_then(|text| {
+                        text.to_uppercase()
+                            .chars()
+                            .filter(|c| FONT.iter().any(|(glyph, _)| glyph == c))
+                            .nth(i)
+                    });
+                match letter {
+                    Some(letter) => {
+                        println!("The message doesn't fit in the maze, at {:?}", letter)
+                    }
+                    None => println!(
+                        "The message doesn't fit in the maze, at part {} of {}",
+                        i + 1,
+                        walks.len()
+                    ),
+                }
+                return VecDeque::new();
+            };
+            // Go around from where the connector arrives. Stopping a cell short leaves a gap
+            // to get out again, in case the outline encloses the walk so far.
+            let entry = connector
+                .last()
+                .and_then(|idx| outline.iter().position(|o| o == idx))
+                .unwrap_or(0);
+            let around = outline[entry + 1..].iter().chain(&outline[..entry]);
+            walk.extend(connector);
+            traced = walk.len();
+            walk.extend(around.take(outline.len().saturating_sub(2)));
+            walk = erase_loops(walk);
+            traced = traced.min(walk.len());
+        }
+
+        let is_border = |idx: usize| {
+            let cell = &self.cells[idx];
+            cell.col == 0 || cell.row == 0 || cell.col == self.cols - 1 || cell.row == self.rows - 1
+        };
+        let Some(connector) = self.connect(&mut walk, traced, &room, &HashSet::new(), is_border)
+        else {
+            println!("The message doesn't fit in the maze");
+            return VecDeque::new();
+        };
+        walk.extend(connector);
+
+        self.planned_exit = walk.last().copied();
+        walk.into_iter().skip(1).collect()
+    }
+
+    /// Shortest route from the end of `walk` to a cell that passes `arrived`, around the
+    /// cells already in the walk, the start room and the cells to `avoid`. Ends with the
+    /// arrived cell. When the end of the walk is stuck, it backs up, but only over half of
+    /// the last outline, which starts at `traced`.
+    fn connect(
+        &self,
+        walk: &mut Vec<usize>,
+        traced: usize,
+        room: &HashSet<usize>,
+        avoid: &HashSet<usize>,
+        arrived: impl Fn(usize) -> bool,
+    ) -> Option<Vec<usize>> {
+        let keep = (traced + walk.len().saturating_sub(traced) / 2).max(1);
+        let mut end = walk.len();
+        while end >= keep {
+            if let Some(route) = self.route(&walk[..end], room, avoid, &arrived) {
+                walk.truncate(end);
+                return Some(route);
+            }
+            end -= 1;
+        }
+        None
+    }
+
+    /// Breadth-first search for `connect`, without backing up.
+    fn route(
+        &self,
+        walk: &[usize],
+        room: &HashSet<usize>,
+        avoid: &HashSet<usize>,
+        arrived: impl Fn(usize) -> bool,
+    ) -> Option<Vec<usize>> {
+        let from = *walk.last()?;
+        if walk.len() > 1 && arrived(from) {
+            return Some(vec![]);
+        }
+
+        let mut came_from: HashMap<usize, usize> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(idx) = queue.pop_front() {
+            for next in self.neighbors(idx) {
+                if next == from || came_from.contains_key(&next) || walk.contains(&next) {
+                    continue;
+                }
+                if room.contains(&next) {
+                    continue;
+                }
+                came_from.insert(next, idx);
+
+                if arrived(next) {
+                    let mut route = vec![next];
+                    while let Some(previous) = came_from.get(route.last()?) {
+                        if *previous == from {
+                            break;
+                        }
+                        route.push(*previous);
+                    }
+                    route.reverse();
+                    return Some(route);
+                }
+                if !avoid.contains(&next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,173 @@
+use std::f32::consts::TAU;
+use std::fs;
+
+use nannou::color::Hsla;
+use nannou::geom::Point2;
+use nannou::prelude::pt2;
+
+use crate::polar::polar;
+
+/// What an icon looks like. Outlines are drawn in a box from -1 to 1, with y going up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Heart,
+    Star,
+    House,
+    Key,
+    Flag,
+    /// Closed outlines read from a file. Overlapping outlines cut holes.
+    Path(Vec<Vec<Point2>>),
+}
+
+impl Shape {
+    /// One of the built-in shapes by name, or else a file with one outline per line, every
+    /// point written as `x,y` and the points separated by spaces.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "heart" => Some(Shape::Heart),
+            "star" => Some(Shape::Star),
+            "house" => Some(Shape::House),
+            "key" => Some(Shape::Key),
+            "flag" => Some(Shape::Flag),
+            path => {
+                let text = fs::read_to_string(path).ok()?;
+                let outlines: Vec<Vec<Point2>> = text
+                    .lines()
+                    .map(|line| {
+                        line.split_whitespace()
+                            .filter_map(|point| {
+                                let (x, y) = point.split_once(',')?;
+                                Some(pt2(x.parse().ok()?, y.parse().ok()?))
+                            })
+                            .collect::<Vec<Point2>>()
+                    })
+                    .filter(|outline| outline.len() > 2)
+                    .collect();
+
+                if outlines.is_empty() {
+                    None
+                } else {
+                    Some(Shape::Path(outlines))
+                }
+            }
+        }
+    }
+
+    /// Closed outlines of the shape. The curves of the heart are drawn as curves, these are
+    /// only an approximation.
+    pub fn outlines(&self) -> Vec<Vec<Point2>> {
+        match self {
+            Shape::Heart => {
+                // The same curves as the drawing, at a size of two
+                let top = pt2(0.0, 0.7);
+                let bottom = pt2(0.0, -0.8);
+                let left = [pt2(-0.6, 1.2), pt2(-2.0, 0.2)];
+                let right = [pt2(2.0, 0.2), pt2(0.6, 1.2)];
+                let steps = 16;
+                let mut outline: Vec<Point2> = (0..steps)
+                    .map(|i| cubic_bezier(top, left[0], left[1], bottom, i, steps))
+                    .collect();
+                outline.extend(
+                    (0..steps).map(|i| cubic_bezier(bottom, right[0], right[1], top, i, steps)),
+                );
+                vec![outline]
+            }
+            Shape::Star => vec![(0..10)
+                .map(|i| {
+                    let radius = if i % 2 == 0 { 1.0 } else { 0.4 };
+                    polar(radius, TAU / 4.0 + i as f32 * TAU / 10.0)
+                })
+                .collect()],
+            Shape::House => vec![
+                vec![
+                    pt2(-0.8, -0.9),
+                    pt2(0.8, -0.9),
+                    pt2(0.8, 0.2),
+                    pt2(0.0, 0.95),
+                    pt2(-0.8, 0.2),
+                ],
+                // Window
+                vec![
+                    pt2(-0.25, -0.45),
+                    pt2(0.25, -0.45),
+                    pt2(0.25, 0.05),
+                    pt2(-0.25, 0.05),
+                ],
+            ],
+            Shape::Key => {
+                // The bow is a ring, the shaft leaves it to the right, with two teeth
+                let bow = pt2(-0.5, 0.0);
+                let shaft = 0.1;
+                let attach = (shaft / 0.45f32).asin();
+                let steps = 32;
+                let mut outline: Vec<Point2> = (0..=steps)
+                    .map(|i| {
+                        let angle = attach + (TAU - 2.0 * attach) * i as f32 / steps as f32;
+                        bow + polar(0.45, angle)
+                    })
+                    .collect();
+                outline.extend([
+                    pt2(0.55, -shaft),
+                    pt2(0.55, -0.4),
+                    pt2(0.7, -0.4),
+                    pt2(0.7, -shaft),
+                    pt2(0.8, -shaft),
+                    pt2(0.8, -0.3),
+                    pt2(0.92, -0.3),
+                    pt2(0.92, -shaft),
+                    pt2(1.0, -shaft),
+                    pt2(1.0, shaft),
+                ]);
+                let hole = (0..steps)
+                    .map(|i| bow + polar(0.2, TAU * i as f32 / steps as f32))
+                    .collect();
+                vec![outline, hole]
+            }
+            Shape::Flag => vec![vec![
+                pt2(-0.7, -1.0),
+                pt2(-0.55, -1.0),
+                pt2(-0.55, 0.2),
+                pt2(0.9, 0.575),
+                pt2(-0.55, 0.95),
+                pt2(-0.55, 1.0),
+                pt2(-0.7, 1.0),
+            ]],
+            Shape::Path(outlines) => outlines.clone(),
+        }
+    }
+}
+
+/// Point `i` of `steps` along a cubic Bézier curve.
+fn cubic_bezier(
+    from: Point2,
+    c1: Point2,
+    c2: Point2,
+    to: Point2,
+    i: usize,
+    steps: usize,
+) -> Point2 {
+    let t = i as f32 / steps as f32;
+    let u = 1.0 - t;
+    from * u * u * u + c1 * 3.0 * u * u * t + c2 * 3.0 * u * t * t + to * t * t * t
+}
+
+/// Marks the start and the exit of the maze.
+pub struct Icon {
+    pub shape: Shape,
+    /// Center of the icon, in cells.
+    pub position: Point2,
+    /// Width and height, in cells.
+    pub size: f32,
+    pub color: Hsla,
+}
+
+impl Icon {
+    pub fn new(shape: Shape, position: Point2, size: f32, color: Hsla) -> Self {
+        Self {
+            shape,
+            position,
+            size,
+            color,
+        }
+    }
+}
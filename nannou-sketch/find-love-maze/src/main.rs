@@ -1,26 +1,50 @@
+mod braid;
+mod fabrication;
+mod hidden;
+mod icons;
+mod metrics;
 mod models;
+mod play;
+mod playback;
+mod polar;
+mod render;
+mod solver;
 
+use std::collections::VecDeque;
 use std::env;
 
+use bertools::print::Print;
+use bertools::shapes;
 use bertools::Record;
 use nannou::geom::path::Builder;
+use nannou::lyon::path::iterator::PathIterator;
 use nannou::prelude::*;
 
-use models::Cell;
-use models::Heart;
+use fabrication::Fabrication;
+use icons::Icon;
+use icons::Shape;
+use metrics::Difficulty;
+use models::Layout;
 use models::Model;
+use models::Settings;
+use play::Player;
+use playback::Playback;
+use playback::Step;
+use render::Style;
+use render::DISC_SIDES;
+use render::TOLERANCE;
 
 use bertools::do_save;
 use bertools::Nannou;
 use nannou::rand::rngs::StdRng;
 use nannou::rand::seq::IteratorRandom;
-use nannou::rand::Rng;
 use nannou::rand::SeedableRng;
 
 impl Default for Model {
     fn default() -> Self {
         let cols = 25;
         let rows = 25;
+        let rings = 12;
         let padding_cells = 4;
         let foreground_color = Hsla::new(336.0, 0.80, 0.47, 1.0);
         let background_color = Hsla::new(40.0, 1.0, 0.57, 1.0);
@@ -33,16 +57,38 @@ impl Default for Model {
             background_color,
             foreground_color,
             highlight_color,
-            height: 0.0,
-            width: 0.0,
             cols,
             rows,
             padding_cells,
+            layout: Layout::Square,
+            weave: false,
+            braid: 0.0,
+            sparsify: 0.0,
+            message: None,
+            plan: VecDeque::default(),
+            planned_exit: None,
             cells: Vec::default(),
+            rings,
+            ring_sizes: Vec::default(),
+            polar_cells: Vec::default(),
             stack: Vec::default(),
             current: None,
+            start: None,
+            exit: None,
+            finished: false,
+            history: Vec::default(),
+            steps: 0,
+            script: None,
+            playback: Playback::default(),
+            metrics: None,
+            start_shape: Shape::Heart,
+            exit_shape: Shape::Heart,
             center_icon: None,
             border_icon: None,
+            print: None,
+            player: None,
+            style: Style::Walls,
+            fabrication: Fabrication::default(),
         }
     }
 }
@@ -67,29 +113,139 @@ fn model(app: &App) -> Model {
         .unwrap();
 
     let seed = env::var("SEED").unwrap_or_else(|_| "0".to_string());
+    let settings = Settings {
+        layout: match env::var("LAYOUT").as_deref() {
+            Ok("polar") => Layout::Polar,
+            _ => Layout::Square,
+        },
+        weave: env::var("WEAVE").is_ok(),
+        braid: env::var("BRAID")
+            .ok()
+            .and_then(|b| b.parse().ok())
+            .unwrap_or(0.0),
+        sparsify: env::var("SPARSIFY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0.0),
+        // A shape like heart or star, or initials, traced by the solution
+        message: env::var("MESSAGE").ok(),
+    };
 
     let recorder = if std::env::var("RECORD").is_ok() {
         Some(Record::new(app))
     } else {
         None
     };
-    Model::new(window_height, window_width, seed, recorder)
+    let difficulty = env::var("DIFFICULTY")
+        .ok()
+        .and_then(|d| Difficulty::parse(&d));
+    let mut model = match (env::var("REPLAY"), difficulty) {
+        // Replay a history exported with E, instead of generating a new maze
+        (Ok(path), _) => {
+            let mut model = Model::replay(&path).expect("failed to read maze history");
+            model.recorder = recorder;
+            model
+        }
+        (_, Some(difficulty)) => difficulty.reroll(seed, settings, recorder, 1000),
+        (_, None) => Model::new(seed, settings, recorder),
+    };
+
+    // Press P to print the maze at this size, regardless of the window size
+    model.print = Print::parse(&env::var("PRINT").unwrap_or_else(|_| "A3@300".to_string()));
+
+    // A built-in icon like star, house, key or flag, or a file with outlines
+    let icon = |name: &str| {
+        env::var(name)
+            .ok()
+            .and_then(|icon| Shape::parse(&icon))
+            .unwrap_or(Shape::Heart)
+    };
+    model.set_icons(icon("START_ICON"), icon("EXIT_ICON"));
+
+    // Sizes in millimetres for the DXF and STL files, like cell=10,wall=2,height=8,base=3,kerf=0.2
+    if let Ok(spec) = env::var("FABRICATE") {
+        match Fabrication::parse(&spec) {
+            // The walls would leave no room in the cells, or overlap
+            Some(fabrication) if fabrication.wall_thickness >= fabrication.cell_size => println!(
+                "Walls of {}mm don't fit in cells of {}mm",
+                fabrication.wall_thickness, fabrication.cell_size
+            ),
+            Some(fabrication) => model.fabrication = fabrication,
+            None => println!("Can't read the fabrication sizes {:?}", spec),
+        }
+    }
+
+    // Walls, lines, corridors, pipes or blocks. Press V to try the next one
+    model.style = env::var("STYLE")
+        .ok()
+        .and_then(|style| Style::parse(&style))
+        .unwrap_or(Style::Walls);
+
+    // Skip the generation and go straight to solving the maze, or press Enter later
+    if env::var("PLAY").is_ok() {
+        model.play();
+    }
+    model
 }
 
 fn event(app: &App, model: &mut Model, event: WindowEvent) {
+    // While playing, the arrows and WASD move the player instead
+    if let (Some(_), KeyPressed(key)) = (&model.player, &event) {
+        if let Some((dx, dy)) = play::direction(*key) {
+            model.move_player(dx, dy);
+            return;
+        }
+    }
+
     match event {
         KeyPressed(Key::S) => do_save(app),
+        KeyPressed(Key::P) => do_print(app, model),
+        KeyPressed(Key::F) => {
+            model.generate();
+            match model.fabricate(&app.exe_name().unwrap(), &model.fabrication) {
+                Ok(file_names) => file_names
+                    .iter()
+                    .for_each(|file_name| println!("Saved to file://{}", file_name)),
+                Err(e) => println!("Error exporting for fabrication: {:?}", e),
+            }
+        }
+        KeyPressed(Key::V) => model.style = model.style.next(),
+        KeyPressed(Key::E) => match model.export(&app.exe_name().unwrap()) {
+            Ok(file_name) => println!("Saved to file://{}", file_name),
+            Err(e) => println!("Error exporting maze history: {:?}", e),
+        },
+        // Playback of the generation
+        KeyPressed(Key::Space) => model.playback.paused = !model.playback.paused,
+        KeyPressed(Key::Up) => model.playback.faster(),
+        KeyPressed(Key::Down) => model.playback.slower(),
+        KeyPressed(Key::Period) => {
+            model.playback.paused = true;
+            model.update();
+        }
+        KeyPressed(Key::Comma) => {
+            model.playback.paused = true;
+            model.rewind(model.steps.saturating_sub(1));
+        }
+        KeyPressed(Key::Right) => (0..10).for_each(|_| model.update()),
+        KeyPressed(Key::Left) => model.rewind(model.steps.saturating_sub(10)),
+        KeyPressed(Key::R) => model.rewind(0),
+        KeyPressed(Key::Return) => model.play(),
         KeyPressed(Key::Escape) | Closed => {
             if let Some(recorder) = &model.recorder {
                 recorder.finish();
             }
+            if let Some(print) = &model.print {
+                print.finish(app);
+            }
         }
         _ => (),
     }
 }
 
 fn update(_app: &App, model: &mut Model, _update: Update) {
-    model.update();
+    for _ in 0..model.playback.tick() {
+        model.update();
+    }
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
@@ -97,41 +253,75 @@ fn view(app: &App, model: &Model, frame: Frame) {
     model.view(app, &draw);
     draw.to_frame(app, &frame).unwrap();
 
+    // Pausing doesn't pad the video, and the video ends with the finished maze
     if let Some(recorder) = &model.recorder {
-        recorder.record(app);
+        if !model.playback.paused {
+            recorder.record(app);
+        }
+        if model.finished {
+            app.set_loop_mode(LoopMode::loop_ntimes(0));
+        }
     }
 }
 
-impl Nannou for Model {
-    fn view(&self, app: &App, draw: &Draw) {
-        let half_a_window = pt3(self.width / 2.0, self.height / 2.0, 0.0);
-        let margin = pt3(
-            self.cell_width() * (self.padding_cells / 2) as f32,
-            self.cell_height() * (self.padding_cells / 2) as f32,
-            0.0,
-        );
-        let draw = draw.translate(-half_a_window + margin);
+fn do_print(app: &App, model: &Model) {
+    if let Some(print) = &model.print {
+        let draw = Draw::new();
+        model.render(app, &draw, print.width as f32, print.height as f32);
+        print.capture(app, &draw);
+    }
+}
+
+impl Model {
+    /// From here on, everything is measured in cells. Polar mazes are already centered.
+    fn cell_space(&self, draw: &Draw, width: f32, height: f32) -> Draw {
+        let draw = draw.scale(self.unit(width, height));
+        match self.layout {
+            Layout::Square => draw.xy(-self.size() / 2.0),
+            Layout::Polar => draw,
+        }
+    }
 
+    /// Draw the maze centered on the origin, scaled to fit `width` by `height` pixels.
+    pub fn render(&self, app: &App, draw: &Draw, width: f32, height: f32) {
         draw.background().color(self.background_color);
-        self.cells.iter().for_each(|cell| cell.view(app, &draw));
 
+        let draw = self.cell_space(draw, width, height);
+
+        self.draw_maze(app, &draw);
+
+        let floor = self.floor(&draw);
         self.center_icon
             .iter()
-            .for_each(|icon| icon.view(app, &draw));
+            .for_each(|icon| icon.view(app, &floor));
         self.border_icon
             .iter()
-            .for_each(|icon| icon.view(app, &draw));
+            .for_each(|icon| icon.view(app, &floor));
+    }
+}
+
+impl Nannou for Model {
+    fn view(&self, app: &App, draw: &Draw) {
+        let window = app.window_rect();
+        self.render(app, draw, window.w(), window.h());
 
+        // Below the bottom left corner of the maze
+        let unit = self.unit(window.w(), window.h());
+        let corner = -self.size() * unit / 2.0;
         let text_place = pt2(
-            self.width / 2.0 - self.cell_width() / 4.0,
-            -self.cell_height() * 1.5,
+            corner.x + window.w() / 2.0 - unit / 4.0,
+            corner.y - unit * 1.5,
         );
 
-        draw.text(self.seed.as_str())
+        let seed_line = match &self.metrics {
+            Some(metrics) => format!("{}  {}", self.seed, metrics),
+            None => self.seed.clone(),
+        };
+        draw.text(seed_line.as_str())
             .xy(text_place)
             .align_text_middle_y()
             .left_justify()
-            .width(self.width)
+            .width(window.w())
             .font_size(12)
             .color(self.foreground_color);
 
@@ -139,283 +329,160 @@ impl Nannou for Model {
             .xy(pt2(text_place.x, text_place.y - 20.0))
             .align_text_middle_y()
             .left_justify()
-            .width(self.width)
+            .width(window.w())
             .font_size(12)
             .color(self.foreground_color);
 
-        if self.current.is_none() {
-            app.set_loop_mode(LoopMode::loop_ntimes(0));
+        let mut status = format!("step {} · {}x", self.steps, self.playback.speed);
+        if self.playback.paused {
+            status.push_str(" · paused");
         }
-    }
+        if let Some(player) = &self.player {
+            status = play::format_time(player.elapsed());
+        }
+        draw.text(status.as_str())
+            .xy(pt2(0.0, text_place.y))
+            .align_text_middle_y()
+            .right_justify()
+            .width(self.size().x * unit)
+            .font_size(12)
+            .color(self.foreground_color);
 
-    fn update(&mut self) {
-        if let Some(current_idx) = self.current {
-            let (next_col, next_row) = (current_idx % self.cols, current_idx / self.cols);
-
-            let neighbors = self.unvisited_neighbors(next_col, next_row);
-
-            if !neighbors.is_empty() {
-                let (next_col, next_row) = neighbors.iter().choose(&mut self.rng).unwrap();
-                let next_idx = self.index(*next_col, *next_row).unwrap();
-
-                self.stack.push(current_idx);
-
-                if let Some(next_idx) = self.index(*next_col, *next_row) {
-                    // First, get the values we need to compare
-                    let current_col = self.cells[current_idx as usize].col;
-                    let current_row = self.cells[current_idx as usize].row;
-                    let next_col = self.cells[next_idx].col;
-                    let next_row = self.cells[next_idx].row;
-
-                    self.cells[next_idx].visited = true;
-
-                    let x = next_col - current_col;
-                    let y = next_row - current_row;
-
-                    match (x, y) {
-                        (1, 0) => {
-                            self.cells[current_idx as usize].right_wall = false;
-                            self.cells[next_idx].left_wall = false;
-                        }
-                        (-1, 0) => {
-                            self.cells[current_idx as usize].left_wall = false;
-                            self.cells[next_idx].right_wall = false;
-                        }
-                        (0, 1) => {
-                            self.cells[current_idx as usize].bottom_wall = false;
-                            self.cells[next_idx].top_wall = false;
-                        }
-                        (0, -1) => {
-                            self.cells[current_idx as usize].top_wall = false;
-                            self.cells[next_idx].bottom_wall = false;
-                        }
-                        _ => (),
-                    };
-                }
+        if let Some(player) = &self.player {
+            let draw = self.cell_space(draw, window.w(), window.h());
+            player.view(app, &self.floor(&draw));
 
-                self.current = Some(next_idx as i32);
-            } else if let Some(back) = self.stack.pop() {
-                self.current = Some(back);
-            } else {
-                // Find a random cell at the border
-                let border_cell = self
-                    .cells
+            // Above the top of the maze
+            if let Some(time) = player.finished_in {
+                let best = player
+                    .best_times
                     .iter()
                     .enumerate()
-                    .filter_map(|(idx, cell)| {
-                        if cell.col == 0
-                            || cell.col == self.cols - 1
-                            || cell.row == 0
-                            || cell.row == self.rows - 1
-                        {
-                            Some(idx)
-                        } else {
-                            None
-                        }
-                    })
-                    .choose(&mut self.rng);
-
-                // If we found one, find the outer wall and remove it.
-                // Draw an icon on the border outside the maze
-                let icon_col;
-                let icon_row;
-                if let Some(idx) = border_cell {
-                    let cell = &self.cells[idx].clone();
-                    if cell.col == 0 {
-                        // left col
-                        icon_col = -1;
-                        icon_row = cell.row;
-                        self.cells[idx].left_wall = false;
-                    } else if cell.col == self.cols - 1 {
-                        // right col
-                        icon_col = self.cols;
-                        icon_row = cell.row;
-                        self.cells[idx].right_wall = false;
-                    } else if cell.row == 0 {
-                        // top row
-                        icon_col = cell.col;
-                        icon_row = -1;
-                        self.cells[idx].top_wall = false;
-                    } else {
-                        // bottom row
-                        icon_col = cell.col;
-                        icon_row = self.rows;
-                        self.cells[idx].bottom_wall = false;
-                    };
-
-                    let icon = Heart::new(icon_row, icon_col, cell.height, self.highlight_color);
-                    self.border_icon = Some(icon);
-                }
-
-                self.current = None;
+                    .map(|(place, time)| format!("{}. {}", place + 1, play::format_time(*time)))
+                    .collect::<Vec<String>>()
+                    .join("   ");
+                let message = format!(
+                    "You found love in {}!\nBest times: {}",
+                    play::format_time(time),
+                    best
+                );
+                draw.text(message.as_str())
+                    .xy(pt2(0.0, -corner.y + unit * 1.5))
+                    .align_text_middle_y()
+                    .center_justify()
+                    .width(window.w())
+                    .font_size(16)
+                    .color(player.color);
             }
-        } else {
-            // Find a random cell in the center-ish of the maze
-            let start_col = self
-                .rng
-                .gen_range((self.cols / 4)..(self.cols - self.cols / 4));
-            let start_row = self
-                .rng
-                .gen_range((self.rows / 4)..(self.rows - self.rows / 4));
-            // Put the icon in this start position
-            if let Some(icon) = &mut self.center_icon {
-                icon.col = start_col;
-                icon.row = start_row;
-            }
-            // Take eight cells around the starting cell and the starting cell itself
-            let start_cells = vec![
-                (0, 0),
-                (1, 0),
-                (0, 1),
-                (-1, 0),
-                (0, -1),
-                (1, 1),
-                (-1, 1),
-                (1, -1),
-                (-1, -1),
-            ];
-            let mut last = start_col + start_row * self.cols;
-            for (x, y) in start_cells {
-                if let Some(idx) = self.index(start_col + x, start_row + y) {
-                    self.cells[idx].start = true;
-                    self.cells[idx].visited = true;
-                    self.cells[idx].top_wall = false;
-                    self.cells[idx].right_wall = false;
-                    self.cells[idx].bottom_wall = false;
-                    self.cells[idx].left_wall = false;
-                    last = idx as i32;
-                }
-            }
-
-            self.current = Some(last);
         }
     }
-}
 
-impl Nannou for Cell {
-    fn view(&self, _app: &App, draw: &Draw) {
-        let x = self.col as f32 * self.width;
-        let y = self.row as f32 * self.height;
-
-        let top = pt2(x, y);
-        let right = pt2(x + self.width, y);
-        let bottom = pt2(x + self.width, y + self.height);
-        let left = pt2(x, y + self.height);
-
-        let stroke_weight = self.width / 2.0;
-        let center = pt2(x + self.width / 2.0, y + self.height / 2.0);
-
-        if !self.visited {
-            draw.rect()
-                .xy(center)
-                .w_h(self.width, self.height)
-                .color(self.foreground_color)
-                .stroke_weight(0.0);
+    fn update(&mut self) {
+        if self.finished {
+            return;
         }
 
-        let draw_line = |draw: &Draw, start: Point2, end: Point2| {
-            draw.line()
-                .start(start)
-                .end(end)
-                .color(self.foreground_color)
-                .stroke_weight(stroke_weight);
-
-            // Start and End Caps. Somehow the caps_square() method is not working?
-            draw.rect()
-                .xy(start)
-                .w_h(stroke_weight, stroke_weight)
-                .color(self.foreground_color)
-                .stroke_weight(0.0);
-            draw.rect()
-                .xy(end)
-                .w_h(stroke_weight, stroke_weight)
-                .color(self.foreground_color)
-                .stroke_weight(0.0);
-        };
+        self.steps += 1;
 
-        if self.top_wall {
-            draw_line(draw, top, right);
+        // A loaded history replays one change per step
+        if let Some(script) = &self.script {
+            let step = script.get(self.history.len()).copied();
+            self.apply(step.unwrap_or(Step::Finish));
+            return;
         }
-        if self.right_wall {
-            draw_line(draw, right, bottom);
+
+        if let Some(current_idx) = self.current {
+            let current_idx = current_idx as usize;
+            let neighbors = self.unvisited_neighbors(current_idx);
+
+            if let Some(next_idx) = self.plan.pop_front() {
+                self.apply(Step::Carve {
+                    from: current_idx,
+                    to: next_idx,
+                });
+            } else if let Some(next_idx) = neighbors.into_iter().choose(&mut self.rng) {
+                self.apply(Step::Carve {
+                    from: current_idx,
+                    to: next_idx,
+                });
+            } else if let Some(&back) = self.stack.last() {
+                self.apply(Step::Backtrack { to: back as usize });
+            } else {
+                if let Some(exit) = self.choose_exit() {
+                    self.apply(Step::Exit { cell: exit });
+                }
+                self.braid(self.braid);
+                self.sparsify(self.sparsify);
+                self.apply(Step::Finish);
+            }
+        } else {
+            let start = self.choose_start();
+            self.apply(Step::Start { cell: start });
+            self.plan = self.plan_message();
         }
-        if self.bottom_wall {
-            draw_line(draw, bottom, left);
+    }
+}
+
+impl Nannou for Player {
+    fn view(&self, app: &App, draw: &Draw) {
+        draw.polyline()
+            .weight(0.25)
+            .tolerance(0.25 * TOLERANCE)
+            .join_round()
+            .points(self.trail.iter().copied())
+            .color(self.color);
+
+        if let Some(position) = self.trail.last() {
+            draw.ellipse()
+                .xy(*position)
+                .radius(0.3)
+                .resolution(DISC_SIDES)
+                .color(self.color);
         }
-        if self.left_wall {
-            draw_line(draw, left, top);
+
+        // Hearts bursting out of the border icon, over and over
+        if self.finished_in.is_some() {
+            let burst = app.time % 1.5;
+            for i in 0..8 {
+                let angle = i as f32 * TAU / 8.0 + burst;
+                let position = self.goal + vec2(angle.cos(), angle.sin()) * (1.0 + burst * 2.0);
+                Icon::new(Shape::Heart, position, 0.6, self.color).view(app, draw);
+            }
         }
     }
 
     fn update(&mut self) {}
 }
 
-impl Nannou for Heart {
+impl Nannou for Icon {
     fn view(&self, _app: &nannou::App, draw: &nannou::Draw) {
-        let mut builder = Builder::new().with_svg();
+        let size = self.size;
+        let center = self.position;
 
-        // Extract common values
-        let size = self.height;
-        let half_size = size / 2.0;
-        let quarter_size = size / 4.0;
-
-        let default_model = Model::default();
-        let x;
-        let y;
-        // if one of the x and y is -1 or cols + 1 or rows + 1, then it is a border icon
-        // In that case, we need to move the icon out with half_size
-        if self.col == -1 {
-            x = self.col as f32 * self.height - half_size;
-            y = self.row as f32 * self.height;
-        } else if self.col == default_model.cols {
-            x = self.col as f32 * self.height + half_size;
-            y = self.row as f32 * self.height;
-        } else if self.row == -1 {
-            x = self.col as f32 * self.height;
-            y = self.row as f32 * self.height - half_size;
-        } else if self.row == default_model.rows {
-            x = self.col as f32 * self.height;
-            y = self.row as f32 * self.height + half_size;
-        } else {
-            x = self.col as f32 * self.height;
-            y = self.row as f32 * self.height;
+        // The heart has curves of its own
+        if self.shape == Shape::Heart {
+            shapes::heart(draw, center, size, self.color);
+            return;
         }
 
-        let center = pt2(x + half_size, y + half_size);
-
-        let width_adjustment = 0.6;
-        let height_adjustment = 0.4 * quarter_size;
-
-        // Control points for the Bézier curves
-        let top_center = center + pt2(0.0, quarter_size + height_adjustment);
-        let left_control_1 =
-            center + pt2(-half_size * width_adjustment, half_size + height_adjustment);
-        let left_control_2 = center + pt2(-size, height_adjustment);
-        let bottom_center = center + pt2(0.0, -half_size + height_adjustment);
-        let right_control_1 = center + pt2(size, height_adjustment);
-        let right_control_2 =
-            center + pt2(half_size * width_adjustment, half_size + height_adjustment);
-
-        builder.move_to(top_center.to_array().into());
-
-        // Draw the left half of the heart using Bézier curves
-        builder.cubic_bezier_to(
-            left_control_1.to_array().into(),
-            left_control_2.to_array().into(),
-            bottom_center.to_array().into(),
-        );
-        // Draw the right half of the heart using Bézier curves
-        builder.cubic_bezier_to(
-            right_control_1.to_array().into(),
-            right_control_2.to_array().into(),
-            top_center.to_array().into(),
-        );
+        // Outlines go from -1 to 1, so half the size fits the icon in its box
+        let mut builder = Builder::new().with_svg();
+        for outline in self.shape.outlines() {
+            let mut points = outline.iter().map(|point| center + *point * size / 2.0);
+            if let Some(first) = points.next() {
+                builder.move_to(first.to_array().into());
+                for point in points {
+                    builder.line_to(point.to_array().into());
+                }
+                builder.close();
+            }
+        }
 
         draw.polygon()
-            .stroke_weight(1.0)
+            .stroke_weight(size / 32.0)
             .stroke(self.color)
             .color(self.color)
-            .events(builder.build().iter());
+            .events(builder.build().iter().flattened(size * TOLERANCE / 4.0));
     }
 
     fn update(&mut self) {}
@@ -0,0 +1,200 @@
+use std::fmt;
+use std::ops::Range;
+
+use bertools::Record;
+
+use crate::models::{Model, Settings};
+
+/// How hard a generated maze is to solve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metrics {
+    /// Number of cells on the shortest path from the center to the border.
+    pub solution_length: usize,
+    /// Cells with only one way out.
+    pub dead_ends: usize,
+    /// Share of the cells, that aren't dead ends, where the path splits.
+    pub branching_factor: f32,
+    /// Average length of the side branches that end in a dead end. Mazes with a high river
+    /// factor have few, long, meandering branches; a low one means many short stubs.
+    pub river_factor: f32,
+    /// Longest run of cells without any junction or dead end.
+    pub longest_corridor: usize,
+    /// Number of different paths of `solution_length`. More than one once the maze has loops.
+    pub shortest_paths: usize,
+}
+
+impl Metrics {
+    pub fn measure(model: &Model) -> Self {
+        let degrees: Vec<usize> = (0..model.cell_count())
+            .map(|idx| model.passages(idx).len())
+            .collect();
+
+        let dead_ends: Vec<usize> = (0..degrees.len())
+            .filter(|idx| degrees[*idx] == 1)
+            .collect();
+        let junctions = degrees.iter().filter(|degree| **degree >= 3).count();
+        let paths = degrees.iter().filter(|degree| **degree >= 2).count();
+
+        // Walk from every dead end until the branch joins a junction.
+        let branch_lengths: Vec<usize> = dead_ends
+            .iter()
+            .map(|dead_end| {
+                let mut previous = *dead_end;
+                let mut current = *dead_end;
+                let mut length = 1;
+                while let Some(next) = model
+                    .passages(current)
+                    .into_iter()
+                    .find(|next| *next != previous)
+                {
+                    if degrees[next] != 2 {
+                        break;
+                    }
+                    previous = current;
+                    current = next;
+                    length += 1;
+                }
+                length
+            })
+            .collect();
+
+        // Flood every run of corridor cells once.
+        let mut seen = vec![false; degrees.len()];
+        let mut longest_corridor = 0;
+        for idx in 0..degrees.len() {
+            if seen[idx] || degrees[idx] != 2 {
+                continue;
+            }
+            let mut length = 0;
+            let mut todo = vec![idx];
+            seen[idx] = true;
+            while let Some(current) = todo.pop() {
+                length += 1;
+                for next in model.passages(current) {
+                    if !seen[next] && degrees[next] == 2 {
+                        seen[next] = true;
+                        todo.push(next);
+                    }
+                }
+            }
+            longest_corridor = longest_corridor.max(length);
+        }
+
+        Self {
+            solution_length: model.solve().map(|path| path.len()).unwrap_or(0),
+            dead_ends: dead_ends.len(),
+            branching_factor: if paths > 0 {
+                junctions as f32 / paths as f32
+            } else {
+                0.0
+            },
+            river_factor: if branch_lengths.is_empty() {
+                0.0
+            } else {
+                branch_lengths.iter().sum::<usize>() as f32 / branch_lengths.len() as f32
+            },
+            longest_corridor,
+            shortest_paths: model.count_shortest_paths(),
+        }
+    }
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "solution {} · dead ends {} · branching {:.2} · river {:.1} · corridor {}",
+            self.solution_length,
+            self.dead_ends,
+            self.branching_factor,
+            self.river_factor,
+            self.longest_corridor
+        )?;
+        if self.shortest_paths > 1 {
+            write!(f, " · {} shortest paths", self.shortest_paths)?;
+        }
+        Ok(())
+    }
+}
+
+/// A band the metrics of a maze must fall in. Lengths are relative to the number of cells,
+/// so the same band works for any maze size and layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difficulty {
+    pub solution: Range<f32>,
+    pub dead_ends: Range<f32>,
+}
+
+impl Difficulty {
+    /// Parse `easy`, `medium`, `hard` or a custom solution band like `0.15..0.3`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let any = 0.0..1.0;
+        match name {
+            "easy" => Some(Self {
+                solution: 0.0..0.08,
+                dead_ends: any,
+            }),
+            "medium" => Some(Self {
+                solution: 0.08..0.16,
+                dead_ends: any,
+            }),
+            "hard" => Some(Self {
+                solution: 0.16..1.0,
+                dead_ends: 0.08..1.0,
+            }),
+            custom => {
+                let (min, max) = custom.split_once("..")?;
+                Some(Self {
+                    solution: min.parse().ok()?..max.parse().ok()?,
+                    dead_ends: any,
+                })
+            }
+        }
+    }
+
+    pub fn contains(&self, metrics: &Metrics, cells: usize) -> bool {
+        let cells = cells.max(1) as f32;
+        self.solution
+            .contains(&(metrics.solution_length as f32 / cells))
+            && self.dead_ends.contains(&(metrics.dead_ends as f32 / cells))
+    }
+
+    /// Keep generating mazes from derived seeds until one lands inside the band.
+    /// Gives up after `attempts` and returns the last maze tried.
+    pub fn reroll(
+        &self,
+        seed: String,
+        settings: Settings,
+        recorder: Option<Record>,
+        attempts: usize,
+    ) -> Model {
+        for attempt in 0..attempts {
+            let candidate = if attempt == 0 {
+                seed.clone()
+            } else {
+                format!("{}-{}", seed, attempt)
+            };
+
+            let mut model = Model::new(candidate.clone(), settings.clone(), None);
+            model.generate();
+
+            let fits = model
+                .metrics
+                .as_ref()
+                .map(|metrics| self.contains(metrics, model.cell_count()))
+                .unwrap_or(false);
+
+            if fits || attempt == attempts - 1 {
+                if fits {
+                    println!("Found seed {} after {} attempts", candidate, attempt + 1);
+                } else {
+                    println!("No seed within difficulty after {} attempts", attempts);
+                }
+                model.recorder = recorder;
+                return model;
+            }
+        }
+
+        Model::new(seed, settings, recorder)
+    }
+}
@@ -1,36 +1,101 @@
+use std::collections::VecDeque;
 use std::hash::{Hash, Hasher};
 
-use bertools::Record;
+use bertools::print::Print;
+use bertools::schemes::seeded_colors;
+use bertools::{Nannou, Record};
 use nannou::color::Hsla;
+use nannou::geom::{Point2, Vec2};
+use nannou::prelude::{pt2, vec2};
 use nannou::rand::rngs::StdRng;
+use nannou::rand::seq::IteratorRandom;
 use nannou::rand::{Rng, SeedableRng};
 
+use crate::fabrication::Fabrication;
+use crate::icons::{Icon, Shape};
+use crate::metrics::Metrics;
+use crate::play::Player;
+use crate::playback::{Playback, Step};
+use crate::polar::{polar, PolarCell};
+use crate::render::Style;
+
+/// Width and height of the icons, in cells.
+const ICON_SIZE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Square,
+    Polar,
+}
+
+/// How a maze gets generated, as opposed to what it looks like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub layout: Layout,
+    /// Let corridors tunnel under perpendicular corridors. Square layout only.
+    pub weave: bool,
+    /// Share of the dead ends to knock through, which adds loops.
+    pub braid: f32,
+    /// Share of the cells to cull, by repeatedly removing dead ends.
+    pub sparsify: f32,
+    /// A shape or text for the solution to trace. Square layout only.
+    pub message: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Square,
+            weave: false,
+            braid: 0.0,
+            sparsify: 0.0,
+            message: None,
+        }
+    }
+}
+
+/// A cell where two corridors cross. The variant tells which of the two runs over the top,
+/// the other one tunnels underneath.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Crossing {
+    VerticalOver,
+    HorizontalOver,
+}
+
+impl Crossing {
+    /// Whether a step of `dx`, `dy` through this cell goes through the tunnel.
+    pub fn is_under(&self, dx: i32, dy: i32) -> bool {
+        match self {
+            Crossing::VerticalOver => dx != 0,
+            Crossing::HorizontalOver => dy != 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Cell {
     pub top_wall: bool,
     pub right_wall: bool,
     pub bottom_wall: bool,
     pub left_wall: bool,
+    pub crossing: Option<Crossing>,
     pub visited: bool,
     pub start: bool,
-    pub height: f32,
-    pub width: f32,
     pub col: i32,
     pub row: i32,
     pub foreground_color: Hsla,
 }
 
 impl Cell {
-    pub fn new(col: i32, row: i32, height: f32, width: f32, foreground_color: Hsla) -> Self {
+    pub fn new(col: i32, row: i32, foreground_color: Hsla) -> Self {
         Self {
             top_wall: true,
             right_wall: true,
             bottom_wall: true,
             left_wall: true,
+            crossing: None,
             visited: false,
             start: false,
-            height,
-            width,
             col,
             row,
             foreground_color,
@@ -45,20 +110,49 @@ pub struct Model {
     pub background_color: Hsla,
     pub foreground_color: Hsla,
     pub highlight_color: Hsla,
-    pub height: f32,
-    pub width: f32,
     pub padding_cells: i32,
+    pub layout: Layout,
+    pub weave: bool,
+    pub braid: f32,
+    pub sparsify: f32,
+    pub message: Option<String>,
+    /// Cells to carve before the backtracker takes over, in order.
+    pub plan: VecDeque<usize>,
+    /// Where the planned corridor leaves the maze.
+    pub planned_exit: Option<usize>,
     pub cols: i32,
     pub rows: i32,
     pub cells: Vec<Cell>,
+    pub rings: i32,
+    pub ring_sizes: Vec<i32>,
+    pub polar_cells: Vec<PolarCell>,
     pub stack: Vec<i32>,
     pub current: Option<i32>,
-    pub center_icon: Option<Heart>,
-    pub border_icon: Option<Heart>,
+    pub start: Option<usize>,
+    pub exit: Option<usize>,
+    pub finished: bool,
+    /// Every change made to the maze so far.
+    pub history: Vec<Step>,
+    /// Generation steps taken so far. One step can make several changes.
+    pub steps: usize,
+    /// A loaded history to replay, instead of generating a new maze.
+    pub script: Option<Vec<Step>>,
+    pub playback: Playback,
+    pub metrics: Option<Metrics>,
+    pub start_shape: Shape,
+    pub exit_shape: Shape,
+    pub center_icon: Option<Icon>,
+    pub border_icon: Option<Icon>,
+    pub print: Option<Print>,
+    pub player: Option<Player>,
+    /// How the maze is drawn.
+    pub style: Style,
+    /// Sizes for laser cutting and 3D printing.
+    pub fabrication: Fabrication,
 }
 
 impl Model {
-    pub fn new(height: f32, width: f32, seed: String, recorder: Option<Record>) -> Self {
+    pub fn new(seed: String, settings: Settings, recorder: Option<Record>) -> Self {
         let default = Self::default();
 
         // Convert seed String to a u64
@@ -68,12 +162,8 @@ impl Model {
 
         let mut rng = StdRng::seed_from_u64(seed_n);
 
-        let foreground_color = Hsla::new(
-            default.foreground_color.hue,
-            default.foreground_color.saturation * rng.gen_range(0.8..1.0), // random slightly less saturated
-            default.foreground_color.lightness * rng.gen_range(1.0..1.2), // random Slightly more pastel
-            default.foreground_color.alpha,
-        );
+        let (foreground_color, background_color) =
+            seeded_colors(&mut rng, default.foreground_color, default.background_color);
 
         let highlight_color = Hsla::new(
             foreground_color.hue,
@@ -82,31 +172,28 @@ impl Model {
             foreground_color.alpha,
         );
 
-        let background_color = Hsla::new(
-            default.background_color.hue,
-            default.background_color.saturation * rng.gen_range(0.6..1.0), // random slightly less saturated
-            default.background_color.lightness * rng.gen_range(1.0..1.2),  // random slightly darker
-            default.background_color.alpha,
-        );
-
-        let cell_height = height / (default.rows + default.padding_cells) as f32;
-        let cell_width = width / (default.cols + default.padding_cells) as f32;
-
         let mut cells = vec![];
         for row in 0..default.rows {
             for col in 0..default.cols {
-                cells.push(Cell::new(
-                    col,
-                    row,
-                    cell_height,
-                    cell_width,
+                cells.push(Cell::new(col, row, foreground_color));
+            }
+        }
+
+        let ring_sizes = PolarCell::ring_sizes(default.rings);
+        let mut polar_cells = vec![];
+        for (ring, count) in ring_sizes.iter().enumerate() {
+            let outermost = ring as i32 == default.rings - 1;
+            for index in 0..*count {
+                polar_cells.push(PolarCell::new(
+                    ring as i32,
+                    index,
+                    *count,
+                    outermost,
                     foreground_color,
                 ));
             }
         }
 
-        let icon = Some(Heart::new(0, 0, cell_height, highlight_color));
-
         Self {
             recorder,
             seed: seed.clone(),
@@ -114,20 +201,52 @@ impl Model {
             foreground_color,
             background_color,
             highlight_color,
-            height,
-            width,
+            layout: settings.layout,
+            weave: settings.weave && settings.layout == Layout::Square,
+            braid: settings.braid,
+            sparsify: settings.sparsify,
+            message: settings.message,
             cells,
-            center_icon: icon,
+            ring_sizes,
+            polar_cells,
             ..Self::default()
         }
     }
 
-    pub fn cell_height(&self) -> f32 {
-        self.height / (self.rows + self.padding_cells) as f32
+    /// Size of a cell, or the height of a ring, in pixels when the maze and its padding are
+    /// fitted into `width` by `height` pixels. Everything else is measured in cells.
+    pub fn unit(&self, width: f32, height: f32) -> f32 {
+        match self.layout {
+            Layout::Square => (width / (self.cols + self.padding_cells) as f32)
+                .min(height / (self.rows + self.padding_cells) as f32),
+            Layout::Polar => width.min(height) / (2 * self.rings + self.padding_cells) as f32,
+        }
     }
 
-    pub fn cell_width(&self) -> f32 {
-        self.width / (self.cols + self.padding_cells) as f32
+    /// Size of the maze itself, in cells.
+    pub fn size(&self) -> Vec2 {
+        match self.layout {
+            Layout::Square => vec2(self.cols as f32, self.rows as f32),
+            Layout::Polar => vec2(2.0 * self.rings as f32, 2.0 * self.rings as f32),
+        }
+    }
+
+    /// Center of the cell at `idx`, in cells.
+    pub fn cell_center(&self, idx: usize) -> Point2 {
+        match self.layout {
+            Layout::Square => {
+                let cell = &self.cells[idx];
+                pt2(cell.col as f32 + 0.5, cell.row as f32 + 0.5)
+            }
+            Layout::Polar => {
+                let cell = &self.polar_cells[idx];
+                if cell.ring == 0 {
+                    return pt2(0.0, 0.0);
+                }
+                let (start, end) = cell.angles();
+                polar(cell.inner_radius() + 0.5, (start + end) / 2.0)
+            }
+        }
     }
 
     pub fn index(&self, col: i32, row: i32) -> Option<usize> {
@@ -140,48 +259,369 @@ impl Model {
         }
     }
 
-    pub fn cell_at(&self, col: i32, row: i32) -> Option<&Cell> {
-        self.index(col, row).map(|index| &self.cells[index])
+    pub fn polar_index(&self, ring: i32, index: i32) -> Option<usize> {
+        if ring < 0 || ring > self.rings - 1 {
+            return None;
+        }
+        let offset: i32 = self.ring_sizes[..ring as usize].iter().sum();
+        Some((offset + index.rem_euclid(self.ring_sizes[ring as usize])) as usize)
+    }
+
+    pub fn cell_count(&self) -> usize {
+        match self.layout {
+            Layout::Square => self.cells.len(),
+            Layout::Polar => self.polar_cells.len(),
+        }
     }
 
-    pub(crate) fn unvisited_neighbors(&self, col: i32, row: i32) -> Vec<(i32, i32)> {
-        let mut neighbors = vec![];
-        let directions = vec![
-            (0, -1), // top
-            (1, 0),  // right
-            (0, 1),  // bottom
-            (-1, 0), // left
-        ];
+    /// Run the whole generation at once, instead of one step per frame.
+    pub fn generate(&mut self) {
+        while !self.finished {
+            self.update();
+        }
+    }
 
-        for (dx, dy) in directions {
-            let new_col = col + dx;
-            let new_row = row + dy;
+    pub fn is_visited(&self, idx: usize) -> bool {
+        match self.layout {
+            Layout::Square => self.cells[idx].visited,
+            Layout::Polar => self.polar_cells[idx].visited,
+        }
+    }
 
-            if let Some(cell) = self.cell_at(new_col, new_row) {
-                if !cell.visited {
-                    neighbors.push((new_col, new_row));
+    pub fn visit(&mut self, idx: usize) {
+        match self.layout {
+            Layout::Square => self.cells[idx].visited = true,
+            Layout::Polar => self.polar_cells[idx].visited = true,
+        }
+    }
+
+    /// All cells adjacent to the cell at `idx`, walls or not.
+    pub fn neighbors(&self, idx: usize) -> Vec<usize> {
+        match self.layout {
+            Layout::Square => {
+                let (col, row) = (self.cells[idx].col, self.cells[idx].row);
+                let directions = vec![
+                    (0, -1), // top
+                    (1, 0),  // right
+                    (0, 1),  // bottom
+                    (-1, 0), // left
+                ];
+
+                directions
+                    .into_iter()
+                    .filter_map(|(dx, dy)| self.index(col + dx, row + dy))
+                    .collect()
+            }
+            Layout::Polar => {
+                let cell = &self.polar_cells[idx];
+                let mut neighbors = vec![];
+
+                if cell.ring > 0 {
+                    let inner_count = self.ring_sizes[cell.ring as usize - 1];
+                    neighbors.extend(
+                        self.polar_index(cell.ring - 1, cell.index * inner_count / cell.count),
+                    );
+                }
+                if cell.count > 1 {
+                    neighbors.extend(self.polar_index(cell.ring, cell.index + 1));
+                    neighbors.extend(self.polar_index(cell.ring, cell.index - 1));
+                }
+                if cell.ring < self.rings - 1 {
+                    let ratio = self.ring_sizes[cell.ring as usize + 1] / cell.count;
+                    for child in 0..ratio {
+                        neighbors
+                            .extend(self.polar_index(cell.ring + 1, cell.index * ratio + child));
+                    }
                 }
+
+                neighbors
             }
         }
+    }
+
+    pub(crate) fn unvisited_neighbors(&self, idx: usize) -> Vec<usize> {
+        let mut unvisited: Vec<usize> = self
+            .neighbors(idx)
+            .into_iter()
+            .filter(|neighbor| !self.is_visited(*neighbor))
+            .collect();
+
+        if self.weave {
+            unvisited.extend(self.tunnel_candidates(idx));
+        }
 
-        neighbors
+        unvisited
     }
-}
 
-pub struct Heart {
-    pub row: i32,
-    pub col: i32,
-    pub height: f32,
-    pub color: Hsla,
-}
+    /// Unvisited cells two steps away, that can be reached by tunnelling under a straight
+    /// corridor running across the cell in between.
+    pub fn tunnel_candidates(&self, idx: usize) -> Vec<usize> {
+        let (col, row) = (self.cells[idx].col, self.cells[idx].row);
+        let directions = vec![(0, -1), (1, 0), (0, 1), (-1, 0)];
 
-impl Heart {
-    pub fn new(row: i32, col: i32, size: f32, color: Hsla) -> Self {
-        Self {
-            row,
-            col,
-            height: size,
-            color,
+        directions
+            .into_iter()
+            .filter_map(|(dx, dy)| {
+                let middle = &self.cells[self.index(col + dx, row + dy)?];
+                let beyond = self.index(col + 2 * dx, row + 2 * dy)?;
+
+                let crossed_corridor = if dx != 0 {
+                    !middle.top_wall && !middle.bottom_wall && middle.left_wall && middle.right_wall
+                } else {
+                    !middle.left_wall && !middle.right_wall && middle.top_wall && middle.bottom_wall
+                };
+
+                if middle.visited
+                    && !middle.start
+                    && middle.crossing.is_none()
+                    && crossed_corridor
+                    && !self.cells[beyond].visited
+                {
+                    Some(beyond)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Carve a passage between two adjacent cells, or tunnel under the cell in between when
+    /// they are two steps apart.
+    pub fn link(&mut self, from: usize, to: usize) {
+        if self.layout == Layout::Square {
+            let x = self.cells[to].col - self.cells[from].col;
+            let y = self.cells[to].row - self.cells[from].row;
+
+            if x.abs() == 2 || y.abs() == 2 {
+                let middle = self.index(self.cells[from].col + x / 2, self.cells[from].row + y / 2);
+                if let Some(middle) = middle {
+                    self.set_wall(from, middle, false);
+                    self.set_wall(middle, to, false);
+                    self.cells[middle].crossing = Some(if x != 0 {
+                        Crossing::VerticalOver
+                    } else {
+                        Crossing::HorizontalOver
+                    });
+                }
+                return;
+            }
+        }
+
+        self.set_wall(from, to, false);
+    }
+
+    /// Put the wall between two adjacent cells back.
+    pub fn unlink(&mut self, from: usize, to: usize) {
+        self.set_wall(from, to, true);
+    }
+
+    fn set_wall(&mut self, from: usize, to: usize, wall: bool) {
+        match self.layout {
+            Layout::Square => {
+                let x = self.cells[to].col - self.cells[from].col;
+                let y = self.cells[to].row - self.cells[from].row;
+
+                match (x, y) {
+                    (1, 0) => {
+                        self.cells[from].right_wall = wall;
+                        self.cells[to].left_wall = wall;
+                    }
+                    (-1, 0) => {
+                        self.cells[from].left_wall = wall;
+                        self.cells[to].right_wall = wall;
+                    }
+                    (0, 1) => {
+                        self.cells[from].bottom_wall = wall;
+                        self.cells[to].top_wall = wall;
+                    }
+                    (0, -1) => {
+                        self.cells[from].top_wall = wall;
+                        self.cells[to].bottom_wall = wall;
+                    }
+                    _ => (),
+                };
+            }
+            Layout::Polar => {
+                let (a, b) = (&self.polar_cells[from], &self.polar_cells[to]);
+                if a.ring == b.ring {
+                    // The wall belongs to the cell on the clockwise side of it.
+                    if (a.index + 1) % a.count == b.index {
+                        self.polar_cells[from].ccw_wall = wall;
+                    } else {
+                        self.polar_cells[to].ccw_wall = wall;
+                    }
+                } else if a.ring > b.ring {
+                    self.polar_cells[from].inward_wall = wall;
+                } else {
+                    self.polar_cells[to].inward_wall = wall;
+                }
+            }
+        }
+    }
+
+    /// Pick the cell the center icon goes in.
+    pub fn choose_start(&mut self) -> usize {
+        match self.layout {
+            Layout::Square => {
+                if let Some(start) = self.choose_message_start() {
+                    return start;
+                }
+                // Find a random cell in the center-ish of the maze
+                let start_col = self
+                    .rng
+                    .gen_range((self.cols / 4)..(self.cols - self.cols / 4));
+                let start_row = self
+                    .rng
+                    .gen_range((self.rows / 4)..(self.rows - self.rows / 4));
+                (start_col + start_row * self.cols) as usize
+            }
+            Layout::Polar => 0,
+        }
+    }
+
+    /// Open up the room around the center icon at `start`. Returns the cell to start carving
+    /// from.
+    pub fn open_start(&mut self, start: usize) -> usize {
+        // Put the icon in this start position
+        let icon = Icon::new(
+            self.start_shape.clone(),
+            self.cell_center(start),
+            ICON_SIZE,
+            self.highlight_color,
+        );
+        self.center_icon = Some(icon);
+
+        match self.layout {
+            Layout::Square => {
+                let (start_col, start_row) = (self.cells[start].col, self.cells[start].row);
+                // Take eight cells around the starting cell and the starting cell itself
+                let start_cells = vec![
+                    (0, 0),
+                    (1, 0),
+                    (0, 1),
+                    (-1, 0),
+                    (0, -1),
+                    (1, 1),
+                    (-1, 1),
+                    (1, -1),
+                    (-1, -1),
+                ];
+                self.start = Some(start);
+                let mut last = start;
+                for (x, y) in start_cells {
+                    if let Some(idx) = self.index(start_col + x, start_row + y) {
+                        self.cells[idx].start = true;
+                        self.cells[idx].visited = true;
+                        self.cells[idx].top_wall = false;
+                        self.cells[idx].right_wall = false;
+                        self.cells[idx].bottom_wall = false;
+                        self.cells[idx].left_wall = false;
+                        last = idx;
+                    }
+                }
+                last
+            }
+            Layout::Polar => {
+                // The centre cell and the first ring around it form the room.
+                let room = 1 + self.ring_sizes.get(1).copied().unwrap_or(0) as usize;
+                self.start = Some(start);
+                for idx in 0..room {
+                    let cell = &mut self.polar_cells[idx];
+                    cell.start = true;
+                    cell.visited = true;
+                    cell.inward_wall = false;
+                    cell.ccw_wall = false;
+                }
+                room - 1
+            }
+        }
+    }
+
+    /// Pick a random cell on the border to leave the maze through.
+    pub fn choose_exit(&mut self) -> Option<usize> {
+        if self.planned_exit.is_some() {
+            return self.planned_exit;
+        }
+        match self.layout {
+            Layout::Square => self
+                .cells
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, cell)| {
+                    if cell.col == 0
+                        || cell.col == self.cols - 1
+                        || cell.row == 0
+                        || cell.row == self.rows - 1
+                    {
+                        Some(idx)
+                    } else {
+                        None
+                    }
+                })
+                .choose(&mut self.rng),
+            Layout::Polar => self
+                .polar_cells
+                .iter()
+                .enumerate()
+                .filter(|(_, cell)| cell.ring == self.rings - 1)
+                .map(|(idx, _)| idx)
+                .choose(&mut self.rng),
+        }
+    }
+
+    /// Break through the outer wall of the border cell at `idx` and put the border icon
+    /// just outside of it.
+    pub fn open_exit(&mut self, idx: usize) {
+        self.exit = Some(idx);
+
+        // Half a cell of space between the opening and the icon
+        let beyond_wall = 0.5 + ICON_SIZE / 2.0;
+        let position = match self.layout {
+            Layout::Square => {
+                // Find the outer wall and remove it.
+                let cell = &self.cells[idx].clone();
+                let outward = if cell.col == 0 {
+                    self.cells[idx].left_wall = false;
+                    vec2(-1.0, 0.0)
+                } else if cell.col == self.cols - 1 {
+                    self.cells[idx].right_wall = false;
+                    vec2(1.0, 0.0)
+                } else if cell.row == 0 {
+                    self.cells[idx].top_wall = false;
+                    vec2(0.0, -1.0)
+                } else {
+                    self.cells[idx].bottom_wall = false;
+                    vec2(0.0, 1.0)
+                };
+                self.cell_center(idx) + outward * (0.5 + beyond_wall)
+            }
+            Layout::Polar => {
+                self.polar_cells[idx].outward_wall = false;
+
+                let cell = &self.polar_cells[idx];
+                let (start, end) = cell.angles();
+                polar(cell.outer_radius() + beyond_wall, (start + end) / 2.0)
+            }
+        };
+
+        let icon = Icon::new(
+            self.exit_shape.clone(),
+            position,
+            ICON_SIZE,
+            self.highlight_color,
+        );
+        self.border_icon = Some(icon);
+    }
+
+    /// Change the shapes of the icons, also when they are already placed.
+    pub fn set_icons(&mut self, start: Shape, exit: Shape) {
+        if let Some(icon) = &mut self.center_icon {
+            icon.shape = start.clone();
+        }
+        if let Some(icon) = &mut self.border_icon {
+            icon.shape = exit.clone();
         }
+        self.start_shape = start;
+        self.exit_shape = exit;
     }
 }
@@ -0,0 +1,187 @@
+use std::fs;
+use std::time::{Duration, Instant};
+
+use bertools::saves_location;
+use nannou::color::Hsla;
+use nannou::event::Key;
+use nannou::geom::Point2;
+
+use crate::models::{Layout, Model};
+
+/// Number of times shown in the best times table.
+const BEST_TIMES: usize = 5;
+
+/// Someone trying to find their way from the center icon to the border icon.
+pub struct Player {
+    pub cell: usize,
+    /// The cell before this one. Going back the way it came takes the next way out, when
+    /// there are more ways in the same direction, like the branches out of a polar cell.
+    pub came_from: Option<usize>,
+    /// Centers of every cell walked through, in cells.
+    pub trail: Vec<Point2>,
+    /// Position of the border icon, where the celebration happens.
+    pub goal: Point2,
+    pub color: Hsla,
+    /// The clock starts with the first move.
+    pub started_at: Option<Instant>,
+    pub finished_in: Option<Duration>,
+    /// Fastest times for this seed, fastest first.
+    pub best_times: Vec<Duration>,
+}
+
+impl Player {
+    pub fn elapsed(&self) -> Duration {
+        match (self.finished_in, self.started_at) {
+            (Some(time), _) => time,
+            (None, Some(started_at)) => started_at.elapsed(),
+            (None, None) => Duration::ZERO,
+        }
+    }
+}
+
+/// The step a key moves the player, as columns and rows. Rows go up the screen.
+pub fn direction(key: Key) -> Option<(i32, i32)> {
+    match key {
+        Key::Up | Key::W => Some((0, 1)),
+        Key::Down | Key::S => Some((0, -1)),
+        Key::Left | Key::A => Some((-1, 0)),
+        Key::Right | Key::D => Some((1, 0)),
+        _ => None,
+    }
+}
+
+/// Format a time as minutes, seconds and tenths.
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs_f32();
+    format!("{}:{:04.1}", (seconds / 60.0) as u32, seconds % 60.0)
+}
+
+impl Model {
+    /// Put a player on the center icon. Finishes the maze first, if it is still growing.
+    pub fn play(&mut self) {
+        self.generate();
+
+        let Some(start) = self.start else {
+            return;
+        };
+        let position = self.cell_center(start);
+        let goal = self
+            .border_icon
+            .as_ref()
+            .map(|icon| icon.position)
+            .unwrap_or(position);
+
+        // Stand out from the walls
+        let color = Hsla::new(
+            self.foreground_color.hue.to_positive_degrees() + 180.0,
+            self.foreground_color.saturation,
+            self.foreground_color.lightness,
+            self.foreground_color.alpha,
+        );
+
+        self.player = Some(Player {
+            cell: start,
+            came_from: None,
+            trail: vec![position],
+            goal,
+            color,
+            started_at: None,
+            finished_in: None,
+            best_times: best_times(&self.seed),
+        });
+    }
+
+    /// Move the player one cell in the direction of `dx`, `dy`, unless a wall is in the way.
+    /// Corridors through a tunnel count as one move. Where more than one way goes in that
+    /// direction, coming back and going again takes the next one.
+    pub fn move_player(&mut self, dx: i32, dy: i32) {
+        let Some(player) = &self.player else {
+            return;
+        };
+        if player.finished_in.is_some() {
+            return;
+        }
+
+        let from = player.cell;
+        let mut ways: Vec<usize> = self
+            .passages(from)
+            .into_iter()
+            .filter(|to| self.heads(from, *to, dx, dy))
+            .collect();
+        ways.sort();
+        let to = match ways.iter().position(|way| Some(*way) == player.came_from) {
+            Some(i) => ways[(i + 1) % ways.len()],
+            None => match ways.first() {
+                Some(way) => *way,
+                None => return,
+            },
+        };
+
+        let position = self.cell_center(to);
+        let won = Some(to) == self.exit;
+        let seed = self.seed.clone();
+
+        if let Some(player) = &mut self.player {
+            let started_at = *player.started_at.get_or_insert_with(Instant::now);
+            player.came_from = Some(from);
+            player.cell = to;
+            player.trail.push(position);
+
+            if won {
+                let time = started_at.elapsed();
+                player.finished_in = Some(time);
+                save_time(&seed, time);
+                player.best_times = best_times(&seed);
+            }
+        }
+    }
+
+    /// Whether going from the cell at `from` to the one at `to` is a step in the direction
+    /// of `dx`, `dy`. In a polar maze up and down go out and in, left and right go around.
+    fn heads(&self, from: usize, to: usize, dx: i32, dy: i32) -> bool {
+        match self.layout {
+            Layout::Square => {
+                let (a, b) = (&self.cells[from], &self.cells[to]);
+                (b.col - a.col).signum() == dx && (b.row - a.row).signum() == dy
+            }
+            Layout::Polar => {
+                let (a, b) = (&self.polar_cells[from], &self.polar_cells[to]);
+                match (dx, dy) {
+                    (0, 1) => b.ring > a.ring,
+                    (0, -1) => b.ring < a.ring,
+                    (-1, 0) => a.ring == b.ring && b.index == (a.index + 1) % a.count,
+                    (1, 0) => a.ring == b.ring && (b.index + 1) % b.count == a.index,
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+fn times_location() -> String {
+    format!("{}find-love-maze-times.txt", saves_location())
+}
+
+/// Fastest times for `seed`, read from the times file in the saves folder. Every line holds
+/// a seed and a time in seconds, separated by a tab.
+pub fn best_times(seed: &str) -> Vec<Duration> {
+    let text = fs::read_to_string(times_location()).unwrap_or_default();
+    let mut times: Vec<Duration> = text
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .filter(|(line_seed, _)| *line_seed == seed)
+        .filter_map(|(_, seconds)| seconds.parse::<f32>().ok())
+        .map(Duration::from_secs_f32)
+        .collect();
+    times.sort();
+    times.truncate(BEST_TIMES);
+    times
+}
+
+fn save_time(seed: &str, time: Duration) {
+    let mut text = fs::read_to_string(times_location()).unwrap_or_default();
+    text.push_str(&format!("{}\t{:.2}\n", seed, time.as_secs_f32()));
+    if let Err(e) = fs::write(times_location(), text) {
+        println!("Error saving time: {:?}", e);
+    }
+}
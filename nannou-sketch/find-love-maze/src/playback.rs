@@ -0,0 +1,232 @@
+use std::fs;
+use std::io;
+
+use bertools::{saves_location, Nannou};
+
+use crate::metrics::Metrics;
+use crate::models::{Layout, Model, Settings};
+
+/// One change to the maze. Generating a maze is a list of these, so it can be replayed
+/// without the random number generator that made it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// Open the room around the center icon.
+    Start {
+        cell: usize,
+    },
+    /// Break through from the current cell into an unvisited one and move there.
+    Carve {
+        from: usize,
+        to: usize,
+    },
+    /// Nothing left to carve here, go back to an earlier cell.
+    Backtrack {
+        to: usize,
+    },
+    /// Open the border next to the border icon.
+    Exit {
+        cell: usize,
+    },
+    /// Knock through the wall at the end of a dead end.
+    Knock {
+        from: usize,
+        to: usize,
+    },
+    /// Cut a dead end loose from the maze.
+    Cull {
+        cell: usize,
+    },
+    Finish,
+}
+
+impl Step {
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let kind = words.next()?;
+        let mut number = || words.next()?.parse::<usize>().ok();
+
+        match kind {
+            "start" => Some(Step::Start { cell: number()? }),
+            "carve" => Some(Step::Carve {
+                from: number()?,
+                to: number()?,
+            }),
+            "back" => Some(Step::Backtrack { to: number()? }),
+            "exit" => Some(Step::Exit { cell: number()? }),
+            "knock" => Some(Step::Knock {
+                from: number()?,
+                to: number()?,
+            }),
+            "cull" => Some(Step::Cull { cell: number()? }),
+            "finish" => Some(Step::Finish),
+            _ => None,
+        }
+    }
+
+    fn line(&self) -> String {
+        match self {
+            Step::Start { cell } => format!("start {}", cell),
+            Step::Carve { from, to } => format!("carve {} {}", from, to),
+            Step::Backtrack { to } => format!("back {}", to),
+            Step::Exit { cell } => format!("exit {}", cell),
+            Step::Knock { from, to } => format!("knock {} {}", from, to),
+            Step::Cull { cell } => format!("cull {}", cell),
+            Step::Finish => "finish".to_string(),
+        }
+    }
+}
+
+/// How fast the generation is shown, independent of the frame rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Playback {
+    pub paused: bool,
+    /// Generation steps per frame. Below one, every step stays on screen for a few frames.
+    pub speed: f32,
+    budget: f32,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            budget: 0.0,
+        }
+    }
+}
+
+impl Playback {
+    /// Number of generation steps to take this frame.
+    pub fn tick(&mut self) -> usize {
+        if self.paused {
+            return 0;
+        }
+        self.budget += self.speed;
+        let steps = self.budget.floor();
+        self.budget -= steps;
+        steps as usize
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(64.0);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.0).max(1.0 / 32.0);
+    }
+}
+
+impl Model {
+    /// Make a change to the maze and add it to the history.
+    pub fn apply(&mut self, step: Step) {
+        match step {
+            Step::Start { cell } => {
+                let last = self.open_start(cell);
+                self.current = Some(last as i32);
+            }
+            Step::Carve { from, to } => {
+                self.stack.push(from as i32);
+                self.visit(to);
+                self.link(from, to);
+                self.current = Some(to as i32);
+            }
+            Step::Backtrack { to } => {
+                self.stack.pop();
+                self.current = Some(to as i32);
+            }
+            Step::Exit { cell } => self.open_exit(cell),
+            Step::Knock { from, to } => self.link(from, to),
+            Step::Cull { cell } => {
+                for neighbor in self.passages(cell) {
+                    self.unlink(cell, neighbor);
+                }
+                self.unvisit(cell);
+            }
+            Step::Finish => {
+                self.current = None;
+                self.finished = true;
+                self.metrics = Some(Metrics::measure(self));
+            }
+        }
+        self.history.push(step);
+    }
+
+    pub fn settings(&self) -> Settings {
+        Settings {
+            layout: self.layout,
+            weave: self.weave,
+            braid: self.braid,
+            sparsify: self.sparsify,
+            message: self.message.clone(),
+        }
+    }
+
+    /// Go back to the maze as it was after `steps` generation steps. The maze is regrown
+    /// from the seed, or from the replayed history, so going forward again gives the same maze.
+    pub fn rewind(&mut self, steps: usize) {
+        let mut fresh = Model::new(self.seed.clone(), self.settings(), self.recorder.take());
+        fresh.print = self.print.take();
+        fresh.playback = self.playback;
+        fresh.script = self.script.take();
+        fresh.style = self.style;
+        fresh.fabrication = self.fabrication;
+        fresh.set_icons(self.start_shape.clone(), self.exit_shape.clone());
+
+        while fresh.steps < steps && !fresh.finished {
+            fresh.update();
+        }
+        *self = fresh;
+    }
+
+    /// Write the seed, the settings and every step so far to a text file in the saves folder,
+    /// named after the seed.
+    pub fn export(&self, name: &str) -> io::Result<String> {
+        let mut lines = vec![
+            format!("seed {}", self.seed),
+            format!(
+                "layout {}",
+                match self.layout {
+                    Layout::Square => "square",
+                    Layout::Polar => "polar",
+                }
+            ),
+            format!("weave {}", self.weave),
+            format!("braid {}", self.braid),
+            format!("sparsify {}", self.sparsify),
+        ];
+        if let Some(message) = &self.message {
+            lines.push(format!("message {}", message));
+        }
+        lines.extend(self.history.iter().map(Step::line));
+
+        let file_name = format!("{}{}-{}{}", saves_location(), name, self.seed, ".maze");
+        fs::write(&file_name, lines.join("\n") + "\n")?;
+        Ok(file_name)
+    }
+
+    /// Load an exported history. The maze replays it step by step instead of generating a
+    /// new one, so it can be shown again or printed at any size.
+    pub fn replay(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut seed = String::new();
+        let mut settings = Settings::default();
+        let mut script = vec![];
+
+        for line in text.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "seed" => seed = value.to_string(),
+                "layout" if value == "polar" => settings.layout = Layout::Polar,
+                "weave" => settings.weave = value == "true",
+                "braid" => settings.braid = value.parse().unwrap_or(0.0),
+                "sparsify" => settings.sparsify = value.parse().unwrap_or(0.0),
+                "message" => settings.message = Some(value.to_string()),
+                _ => script.extend(Step::parse(line)),
+            }
+        }
+
+        let mut model = Model::new(seed, settings, None);
+        model.script = Some(script);
+        Ok(model)
+    }
+}
@@ -0,0 +1,73 @@
+use nannou::color::Hsla;
+use nannou::prelude::*;
+
+/// A cell in a circular (theta) maze. Ring 0 is the single cell in the centre.
+/// Each cell owns the wall towards the centre and the wall on its counter-clockwise side.
+/// Only cells in the outermost ring have an outward wall. Rings are one unit high.
+#[derive(Debug, Clone)]
+pub struct PolarCell {
+    pub inward_wall: bool,
+    pub ccw_wall: bool,
+    pub outward_wall: bool,
+    pub visited: bool,
+    pub start: bool,
+    pub ring: i32,
+    pub index: i32,
+    pub count: i32,
+    pub foreground_color: Hsla,
+}
+
+impl PolarCell {
+    pub fn new(ring: i32, index: i32, count: i32, outermost: bool, foreground_color: Hsla) -> Self {
+        Self {
+            inward_wall: ring > 0,
+            ccw_wall: count > 1,
+            outward_wall: outermost,
+            visited: false,
+            start: false,
+            ring,
+            index,
+            count,
+            foreground_color,
+        }
+    }
+
+    pub fn inner_radius(&self) -> f32 {
+        self.ring as f32
+    }
+
+    pub fn outer_radius(&self) -> f32 {
+        (self.ring + 1) as f32
+    }
+
+    /// Start and end angle in radians, counter-clockwise.
+    pub fn angles(&self) -> (f32, f32) {
+        let theta = TAU / self.count as f32;
+        (self.index as f32 * theta, (self.index + 1) as f32 * theta)
+    }
+
+    /// Number of cells in each ring, starting with the single centre cell. A ring gets
+    /// subdivided as soon as its cells would become about twice as wide as they are high.
+    pub fn ring_sizes(rings: i32) -> Vec<i32> {
+        let mut sizes = vec![1];
+        for ring in 1..rings {
+            let circumference = TAU * ring as f32;
+            let previous = sizes[ring as usize - 1];
+            let ratio = (circumference / previous as f32).round().max(1.0) as i32;
+            sizes.push(previous * ratio);
+        }
+        sizes
+    }
+}
+
+pub fn polar(radius: f32, angle: f32) -> Point2 {
+    pt2(radius * angle.cos(), radius * angle.sin())
+}
+
+pub fn arc(radius: f32, start: f32, end: f32) -> Vec<Point2> {
+    // One point per degree keeps the arcs smooth, even when printed large.
+    let resolution = rad_to_deg((end - start).abs()).ceil().max(1.0) as usize;
+    (0..=resolution)
+        .map(|i| polar(radius, map_range(i, 0, resolution, start, end)))
+        .collect()
+}
@@ -0,0 +1,646 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use nannou::color::Hsla;
+use nannou::prelude::*;
+
+use bertools::Nannou;
+
+use crate::models::{Cell, Crossing, Layout, Model};
+use crate::polar::{arc, polar, PolarCell};
+
+/// Width of the corridors, in cells.
+const CORRIDOR_WIDTH: f32 = 0.6;
+/// Width of the pipes, in cells.
+const PIPE_WIDTH: f32 = 0.7;
+/// Thickness and height of the isometric walls, in cells.
+const BLOCK_THICKNESS: f32 = 0.3;
+const BLOCK_HEIGHT: f32 = 0.6;
+/// Everything is drawn in cells and scaled up after lyon has cut the curves into straight
+/// pieces, so how close they follow the curve goes with the width of the line, and discs
+/// get a fixed number of sides. That way they are as smooth on a large print as on screen.
+pub const TOLERANCE: f32 = 0.002;
+pub const DISC_SIDES: f32 = 48.0;
+
+/// How the maze is drawn. The model only keeps track of the walls and the passages, every
+/// style makes its own picture of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// Thick walls, with solid blocks for cells that are not part of the maze.
+    Walls,
+    /// Thin walls, like a maze in a puzzle book.
+    Lines,
+    /// The passages instead of the walls, with rounded corners.
+    Corridors,
+    /// The passages as pipes.
+    Pipes,
+    /// The walls as blocks, seen from an isometric angle.
+    Blocks,
+}
+
+impl Style {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "walls" => Some(Style::Walls),
+            "lines" => Some(Style::Lines),
+            "corridors" => Some(Style::Corridors),
+            "pipes" => Some(Style::Pipes),
+            "blocks" => Some(Style::Blocks),
+            _ => None,
+        }
+    }
+
+    /// The style after this one, to cycle through all of them.
+    pub fn next(self) -> Self {
+        match self {
+            Style::Walls => Style::Lines,
+            Style::Lines => Style::Corridors,
+            Style::Corridors => Style::Pipes,
+            Style::Pipes => Style::Blocks,
+            Style::Blocks => Style::Walls,
+        }
+    }
+}
+
+/// Center lines of the passages, cut into pieces that end on the edges of the cells.
+struct Network {
+    paths: Vec<Vec<Point2>>,
+    /// Cell centers where pieces meet, which need a round joint.
+    joints: Vec<Point2>,
+    /// Passages over the top of a crossing, drawn after everything else.
+    bridges: Vec<Vec<Point2>>,
+}
+
+/// A prism standing on a floor outline, in cells.
+struct Block {
+    outline: Vec<Point2>,
+    bottom: f32,
+    top: f32,
+    color: Hsla,
+}
+
+impl Model {
+    /// Draw the maze in the current style. Everything is measured in cells.
+    pub fn draw_maze(&self, app: &App, draw: &Draw) {
+        match self.style {
+            Style::Walls => match self.layout {
+                Layout::Square => self.cells.iter().for_each(|cell| cell.view(app, draw)),
+                Layout::Polar => self
+                    .polar_cells
+                    .iter()
+                    .for_each(|cell| cell.view(app, draw)),
+            },
+            Style::Lines => self.draw_lines(draw),
+            Style::Corridors => self.draw_corridors(draw),
+            Style::Pipes => self.draw_pipes(draw),
+            Style::Blocks => self.draw_blocks(draw),
+        }
+    }
+
+    /// Where things on the ground go, like the icons and the player. Only the blocks are not
+    /// seen from straight above.
+    pub fn floor(&self, draw: &Draw) -> Draw {
+        match self.style {
+            Style::Blocks => {
+                // The same projection as `project`, at a height of zero
+                let middle = self.middle();
+                draw.xy(middle)
+                    .scale(self.shrink())
+                    .scale_x(1.5f32.sqrt())
+                    .scale_y(FRAC_1_SQRT_2)
+                    .rotate(PI / 4.0)
+                    .xy(-middle)
+            }
+            _ => draw.clone(),
+        }
+    }
+
+    fn middle(&self) -> Point2 {
+        match self.layout {
+            Layout::Square => self.size() / 2.0,
+            Layout::Polar => pt2(0.0, 0.0),
+        }
+    }
+
+    /// How much smaller the isometric view is drawn, to fit the same space as the maze.
+    fn shrink(&self) -> f32 {
+        let cos_30 = (PI / 6.0).cos();
+        match self.layout {
+            Layout::Square => self.size().x / ((self.size().x + self.size().y) * cos_30),
+            Layout::Polar => FRAC_1_SQRT_2 / cos_30,
+        }
+    }
+
+    /// A point `height` cells above the floor, as seen from an isometric angle. The far
+    /// corner of the maze ends up at the top.
+    fn project(&self, point: Point2, height: f32) -> Point2 {
+        let middle = self.middle();
+        let p = point - middle;
+        let (sin_30, cos_30) = (PI / 6.0).sin_cos();
+        middle + pt2((p.x - p.y) * cos_30, (p.x + p.y) * sin_30 + height) * self.shrink()
+    }
+
+    /// Every wall as a line along its middle. Walls along a ring are arcs.
+    fn wall_lines(&self) -> Vec<Vec<Point2>> {
+        let mut lines = vec![];
+        match self.layout {
+            Layout::Square => {
+                for cell in &self.cells {
+                    let (x, y) = (cell.col as f32, cell.row as f32);
+
+                    // Walls between two cells are on both of them, only draw them once
+                    if self.has_wall(cell, 0, -1) {
+                        lines.push(vec![pt2(x, y), pt2(x + 1.0, y)]);
+                    }
+                    if self.has_wall(cell, -1, 0) {
+                        lines.push(vec![pt2(x, y), pt2(x, y + 1.0)]);
+                    }
+                    if cell.bottom_wall && cell.row == self.rows - 1 {
+                        lines.push(vec![pt2(x, y + 1.0), pt2(x + 1.0, y + 1.0)]);
+                    }
+                    if cell.right_wall && cell.col == self.cols - 1 {
+                        lines.push(vec![pt2(x + 1.0, y), pt2(x + 1.0, y + 1.0)]);
+                    }
+                }
+            }
+            Layout::Polar => {
+                for cell in &self.polar_cells {
+                    let (start, end) = cell.angles();
+                    let (inner, outer) = (cell.inner_radius(), cell.outer_radius());
+
+                    if cell.inward_wall {
+                        lines.push(arc(inner, start, end));
+                    }
+                    if cell.ccw_wall {
+                        lines.push(vec![polar(inner, end), polar(outer, end)]);
+                    }
+                    if cell.outward_wall {
+                        lines.push(arc(outer, start, end));
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    /// Whether there is a wall on the side of `cell` towards `dx`, `dy`. The room around the
+    /// center icon only takes down the walls on its own side, so the neighbor is asked too.
+    pub fn has_wall(&self, cell: &Cell, dx: i32, dy: i32) -> bool {
+        let own = match (dx, dy) {
+            (0, -1) => cell.top_wall,
+            (1, 0) => cell.right_wall,
+            (0, 1) => cell.bottom_wall,
+            _ => cell.left_wall,
+        };
+        let neighbor = self
+            .index(cell.col + dx, cell.row + dy)
+            .map(|idx| match (dx, dy) {
+                (0, -1) => self.cells[idx].bottom_wall,
+                (1, 0) => self.cells[idx].left_wall,
+                (0, 1) => self.cells[idx].top_wall,
+                _ => self.cells[idx].right_wall,
+            })
+            .unwrap_or(false);
+        own || neighbor
+    }
+
+    fn draw_lines(&self, draw: &Draw) {
+        let weight = 0.1;
+        let mut lines = self.wall_lines();
+        for cell in &self.cells {
+            lines.extend(
+                cell.bridge(0.2)
+                    .into_iter()
+                    .flatten()
+                    .map(|(start, end)| vec![start, end]),
+            );
+        }
+
+        for line in lines {
+            draw.polyline()
+                .weight(weight)
+                .tolerance(weight * TOLERANCE)
+                .caps_round()
+                .points(line)
+                .color(self.foreground_color);
+        }
+    }
+
+    /// The passages of every visited cell. Square cells with a single bend get a quarter
+    /// circle when `rounded`, polar passages always follow the rings.
+    fn network(&self, rounded: bool) -> Network {
+        let mut network = Network {
+            paths: vec![],
+            joints: vec![],
+            bridges: vec![],
+        };
+
+        match self.layout {
+            Layout::Square => {
+                for cell in self.cells.iter().filter(|cell| cell.visited) {
+                    let center = pt2(cell.col as f32 + 0.5, cell.row as f32 + 0.5);
+                    // From the center to the middle of every open side, the exit included
+                    let openings: Vec<Vec2> = [(0, -1), (1, 0), (0, 1), (-1, 0)]
+                        .into_iter()
+                        .filter(|(dx, dy)| !self.has_wall(cell, *dx, *dy))
+                        .map(|(dx, dy)| vec2(dx as f32, dy as f32) / 2.0)
+                        .collect();
+
+                    match (cell.crossing, openings.as_slice()) {
+                        (Some(crossing), _) => {
+                            let (over, under) = match crossing {
+                                Crossing::VerticalOver => (vec2(0.0, 0.5), vec2(0.5, 0.0)),
+                                Crossing::HorizontalOver => (vec2(0.5, 0.0), vec2(0.0, 0.5)),
+                            };
+                            network.paths.push(vec![center - under, center + under]);
+                            network.bridges.push(vec![center - over, center + over]);
+                        }
+                        (None, [a, b]) if rounded && a.dot(*b) == 0.0 => {
+                            // Around the corner of the cell between the two openings
+                            let corner = center + *a + *b;
+                            let start = (-b.y).atan2(-b.x);
+                            let mut end = (-a.y).atan2(-a.x);
+                            if end - start > PI {
+                                end -= TAU;
+                            } else if start - end > PI {
+                                end += TAU;
+                            }
+                            network.paths.push(
+                                arc(0.5, start, end)
+                                    .into_iter()
+                                    .map(|point| corner + point)
+                                    .collect(),
+                            );
+                        }
+                        (None, openings) => {
+                            for side in openings {
+                                network.paths.push(vec![center, center + *side]);
+                            }
+                            network.joints.push(center);
+                        }
+                    }
+                }
+            }
+            Layout::Polar => {
+                for (idx, cell) in self.polar_cells.iter().enumerate() {
+                    if !cell.visited {
+                        continue;
+                    }
+                    let center = self.cell_center(idx);
+                    let middle = cell.inner_radius() + 0.5;
+                    let (start, end) = cell.angles();
+                    let angle = (start + end) / 2.0;
+
+                    for to in self.passages(idx) {
+                        let other = &self.polar_cells[to];
+                        let (other_start, other_end) = other.angles();
+                        let other_angle = (other_start + other_end) / 2.0;
+
+                        let path = if other.ring == cell.ring {
+                            // Along the ring, up to the wall in between
+                            let edge = if (cell.index + 1) % cell.count == other.index {
+                                end
+                            } else {
+                                start
+                            };
+                            arc(middle, angle, edge)
+                        } else if cell.ring == 0 {
+                            vec![center, polar(1.0, other_angle)]
+                        } else if other.ring < cell.ring {
+                            // Straight in, the cell further in bends towards this one
+                            vec![center, polar(cell.inner_radius(), angle)]
+                        } else {
+                            let mut path = arc(middle, angle, other_angle);
+                            path.push(polar(cell.outer_radius(), other_angle));
+                            path
+                        };
+                        network.paths.push(path);
+                    }
+
+                    if cell.ring == self.rings - 1 && !cell.outward_wall {
+                        network
+                            .paths
+                            .push(vec![center, polar(cell.outer_radius(), angle)]);
+                    }
+                    network.joints.push(center);
+                }
+            }
+        }
+        network
+    }
+
+    fn draw_corridors(&self, draw: &Draw) {
+        let network = self.network(true);
+        stroke(
+            draw,
+            &network.paths,
+            &network.joints,
+            CORRIDOR_WIDTH,
+            self.foreground_color,
+        );
+
+        // A rim in the background color lifts the bridges off the corridors underneath
+        stroke(
+            draw,
+            &network.bridges,
+            &[],
+            CORRIDOR_WIDTH + 0.2,
+            self.background_color,
+        );
+        stroke(
+            draw,
+            &network.bridges,
+            &[],
+            CORRIDOR_WIDTH,
+            self.foreground_color,
+        );
+    }
+
+    fn draw_pipes(&self, draw: &Draw) {
+        let network = self.network(false);
+        let rim = shade(self.foreground_color, 0.6);
+        let shine = shade(self.foreground_color, 1.5);
+
+        for (paths, joints) in [
+            (&network.paths, network.joints.as_slice()),
+            (&network.bridges, &[][..]),
+        ] {
+            stroke(draw, paths, joints, PIPE_WIDTH + 0.1, rim);
+            stroke(draw, paths, joints, PIPE_WIDTH, self.foreground_color);
+            stroke(draw, paths, joints, PIPE_WIDTH / 4.0, shine);
+        }
+    }
+
+    fn draw_blocks(&self, draw: &Draw) {
+        let mut blocks = vec![];
+
+        for line in self.wall_lines() {
+            for pair in line.windows(2) {
+                blocks.push(Block {
+                    outline: slab(pair[0], pair[1], BLOCK_THICKNESS),
+                    bottom: 0.0,
+                    top: BLOCK_HEIGHT,
+                    color: self.foreground_color,
+                });
+            }
+        }
+
+        // Cells that are not part of the maze are solid
+        match self.layout {
+            Layout::Square => {
+                for cell in self.cells.iter().filter(|cell| !cell.visited) {
+                    let (x, y) = (cell.col as f32, cell.row as f32);
+                    blocks.push(Block {
+                        outline: vec![
+                            pt2(x, y),
+                            pt2(x + 1.0, y),
+                            pt2(x + 1.0, y + 1.0),
+                            pt2(x, y + 1.0),
+                        ],
+                        bottom: 0.0,
+                        top: BLOCK_HEIGHT,
+                        color: self.foreground_color,
+                    });
+                }
+            }
+            Layout::Polar => {
+                for cell in self.polar_cells.iter().filter(|cell| !cell.visited) {
+                    let (start, end) = cell.angles();
+                    let mut outline = arc(cell.outer_radius(), start, end);
+                    outline.extend(arc(cell.inner_radius(), end, start));
+                    blocks.push(Block {
+                        outline,
+                        bottom: 0.0,
+                        top: BLOCK_HEIGHT,
+                        color: self.foreground_color,
+                    });
+                }
+            }
+        }
+
+        // Low railings along the corridor on top of a crossing
+        for cell in &self.cells {
+            for (start, end) in cell.bridge(BLOCK_THICKNESS).into_iter().flatten() {
+                blocks.push(Block {
+                    outline: slab(start, end, BLOCK_THICKNESS / 2.0),
+                    bottom: 0.0,
+                    top: BLOCK_HEIGHT / 2.0,
+                    color: shade(self.foreground_color, 1.3),
+                });
+            }
+        }
+
+        // Back to front, so the blocks in front cover the ones behind them
+        let depth = |block: &Block| {
+            let sum: Vec2 = block.outline.iter().sum();
+            let center = sum / block.outline.len() as f32;
+            center.x + center.y
+        };
+        blocks.sort_by(|a, b| depth(b).total_cmp(&depth(a)));
+
+        for block in &blocks {
+            self.draw_block(draw, block);
+        }
+    }
+
+    fn draw_block(&self, draw: &Draw, block: &Block) {
+        // Counter-clockwise, so the normals point outward
+        let mut outline = block.outline.clone();
+        let area: f32 = outline
+            .iter()
+            .zip(outline.iter().cycle().skip(1))
+            .map(|(p, q)| p.x * q.y - q.x * p.y)
+            .sum();
+        if area < 0.0 {
+            outline.reverse();
+        }
+
+        for (p, q) in outline.iter().zip(outline.iter().cycle().skip(1)) {
+            let normal = vec2(q.y - p.y, p.x - q.x);
+            // Only the sides facing the bottom of the screen are seen, the left ones lighter
+            if normal.x + normal.y < 0.0 {
+                let normal = normal.normalize();
+                draw.polygon()
+                    .points([
+                        self.project(*p, block.bottom),
+                        self.project(*q, block.bottom),
+                        self.project(*q, block.top),
+                        self.project(*p, block.top),
+                    ])
+                    .color(shade(block.color, 0.6 + 0.2 * (normal.y - normal.x)));
+            }
+        }
+
+        draw.polygon()
+            .points(outline.iter().map(|point| self.project(*point, block.top)))
+            .color(block.color);
+    }
+}
+
+/// Draw every path with the same weight, and a disc on every joint to round it off.
+fn stroke(draw: &Draw, paths: &[Vec<Point2>], joints: &[Point2], weight: f32, color: Hsla) {
+    for path in paths {
+        draw.polyline()
+            .weight(weight)
+            .tolerance(weight * TOLERANCE)
+            .join_round()
+            .points(path.iter().copied())
+            .color(color);
+    }
+    for joint in joints {
+        draw.ellipse()
+            .xy(*joint)
+            .radius(weight / 2.0)
+            .resolution(DISC_SIDES)
+            .color(color);
+    }
+}
+
+/// Rectangle around the line from `start` to `end`, sticking out half its thickness at both
+/// ends so walls meeting at a corner overlap. Counter-clockwise.
+fn slab(start: Point2, end: Point2, thickness: f32) -> Vec<Point2> {
+    let along = (end - start).normalize() * thickness / 2.0;
+    let across = vec2(-along.y, along.x);
+    vec![
+        start - along - across,
+        end + along - across,
+        end + along + across,
+        start - along + across,
+    ]
+}
+
+/// The same color, lighter or darker.
+fn shade(color: Hsla, factor: f32) -> Hsla {
+    let mut shaded = color;
+    shaded.lightness = (color.lightness * factor).min(1.0);
+    shaded
+}
+
+impl Cell {
+    /// The sides of the corridor on top, when this cell is a crossing. They are `inset` from
+    /// the walls, which leaves a gap in the wall lines of the corridor underneath.
+    pub fn bridge(&self, inset: f32) -> Option<[(Point2, Point2); 2]> {
+        let (x, y) = (self.col as f32, self.row as f32);
+        self.crossing.map(|crossing| match crossing {
+            Crossing::VerticalOver => [
+                (pt2(x + inset, y), pt2(x + inset, y + 1.0)),
+                (pt2(x + 1.0 - inset, y), pt2(x + 1.0 - inset, y + 1.0)),
+            ],
+            Crossing::HorizontalOver => [
+                (pt2(x, y + inset), pt2(x + 1.0, y + inset)),
+                (pt2(x, y + 1.0 - inset), pt2(x + 1.0, y + 1.0 - inset)),
+            ],
+        })
+    }
+}
+
+impl Nannou for Cell {
+    fn view(&self, _app: &App, draw: &Draw) {
+        let x = self.col as f32;
+        let y = self.row as f32;
+
+        let top = pt2(x, y);
+        let right = pt2(x + 1.0, y);
+        let bottom = pt2(x + 1.0, y + 1.0);
+        let left = pt2(x, y + 1.0);
+
+        let stroke_weight = 0.5;
+        let center = pt2(x + 0.5, y + 0.5);
+
+        if !self.visited {
+            draw.rect()
+                .xy(center)
+                .w_h(1.0, 1.0)
+                .color(self.foreground_color)
+                .stroke_weight(0.0);
+        }
+
+        let draw_line = |draw: &Draw, start: Point2, end: Point2| {
+            draw.line()
+                .start(start)
+                .end(end)
+                .color(self.foreground_color)
+                .stroke_weight(stroke_weight);
+
+            // Start and End Caps. Somehow the caps_square() method is not working?
+            draw.rect()
+                .xy(start)
+                .w_h(stroke_weight, stroke_weight)
+                .color(self.foreground_color)
+                .stroke_weight(0.0);
+            draw.rect()
+                .xy(end)
+                .w_h(stroke_weight, stroke_weight)
+                .color(self.foreground_color)
+                .stroke_weight(0.0);
+        };
+
+        if self.top_wall {
+            draw_line(draw, top, right);
+        }
+        if self.right_wall {
+            draw_line(draw, right, bottom);
+        }
+        if self.bottom_wall {
+            draw_line(draw, bottom, left);
+        }
+        if self.left_wall {
+            draw_line(draw, left, top);
+        }
+
+        // The corridor on top gets thin walls along its sides
+        for (start, end) in self.bridge(stroke_weight / 2.0).into_iter().flatten() {
+            draw.line()
+                .start(start)
+                .end(end)
+                .color(self.foreground_color)
+                .stroke_weight(stroke_weight / 4.0);
+        }
+    }
+
+    fn update(&mut self) {}
+}
+
+impl Nannou for PolarCell {
+    fn view(&self, _app: &App, draw: &Draw) {
+        let (start, end) = self.angles();
+        let inner = self.inner_radius();
+        let outer = self.outer_radius();
+        let stroke_weight = 0.5;
+
+        if !self.visited {
+            let mut points = arc(inner, start, end);
+            points.extend(arc(outer, end, start));
+            draw.polygon().points(points).color(self.foreground_color);
+        }
+
+        let draw_wall = |draw: &Draw, points: Vec<Point2>| {
+            let first = points[0];
+            let last = points[points.len() - 1];
+            draw.polyline()
+                .weight(stroke_weight)
+                .points(points)
+                .color(self.foreground_color);
+
+            // Round caps, so arcs and radial walls join without notches.
+            for cap in [first, last] {
+                draw.ellipse()
+                    .xy(cap)
+                    .radius(stroke_weight / 2.0)
+                    .resolution(DISC_SIDES)
+                    .color(self.foreground_color);
+            }
+        };
+
+        if self.inward_wall {
+            draw_wall(draw, arc(inner, start, end));
+        }
+        if self.ccw_wall {
+            draw_wall(draw, vec![polar(inner, end), polar(outer, end)]);
+        }
+        if self.outward_wall {
+            draw_wall(draw, arc(outer, start, end));
+        }
+    }
+
+    fn update(&mut self) {}
+}
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+use crate::models::{Layout, Model};
+
+impl Model {
+    /// Whether there is no wall between two adjacent cells.
+    pub fn is_open(&self, from: usize, to: usize) -> bool {
+        match self.layout {
+            Layout::Square => {
+                let (a, b) = (&self.cells[from], &self.cells[to]);
+                let (dx, dy) = (b.col - a.col, b.row - a.row);
+
+                // Stepping into or out of a tunnel sideways means hitting the bridge.
+                let tunnelled = [a.crossing, b.crossing]
+                    .iter()
+                    .flatten()
+                    .any(|crossing| crossing.is_under(dx, dy));
+                if tunnelled {
+                    return false;
+                }
+
+                match (dx, dy) {
+                    (1, 0) => !a.right_wall && !b.left_wall,
+                    (-1, 0) => !a.left_wall && !b.right_wall,
+                    (0, 1) => !a.bottom_wall && !b.top_wall,
+                    (0, -1) => !a.top_wall && !b.bottom_wall,
+                    _ => false,
+                }
+            }
+            Layout::Polar => {
+                let (a, b) = (&self.polar_cells[from], &self.polar_cells[to]);
+                if a.ring == b.ring {
+                    if (a.index + 1) % a.count == b.index {
+                        !a.ccw_wall
+                    } else {
+                        !b.ccw_wall
+                    }
+                } else if a.ring > b.ring {
+                    !a.inward_wall
+                } else {
+                    !b.inward_wall
+                }
+            }
+        }
+    }
+
+    /// Cells that can be reached from the cell at `idx` in one step, including the cells on
+    /// the other side of a tunnel.
+    pub fn passages(&self, idx: usize) -> Vec<usize> {
+        let mut passages: Vec<usize> = self
+            .neighbors(idx)
+            .into_iter()
+            .filter(|neighbor| self.is_open(idx, *neighbor))
+            .collect();
+
+        if self.weave {
+            passages.extend(self.tunnels(idx));
+        }
+
+        passages
+    }
+
+    /// Cells on the other side of a crossing that tunnels away from the cell at `idx`.
+    pub fn tunnels(&self, idx: usize) -> Vec<usize> {
+        let (col, row) = (self.cells[idx].col, self.cells[idx].row);
+        let directions = vec![(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+        directions
+            .into_iter()
+            .filter_map(|(dx, dy)| {
+                let middle = &self.cells[self.index(col + dx, row + dy)?];
+                let beyond = self.index(col + 2 * dx, row + 2 * dy)?;
+                let under = middle
+                    .crossing
+                    .map(|crossing| crossing.is_under(dx, dy))
+                    .unwrap_or(false);
+
+                let open = match (dx, dy) {
+                    (1, 0) => !self.cells[idx].right_wall && !self.cells[beyond].left_wall,
+                    (-1, 0) => !self.cells[idx].left_wall && !self.cells[beyond].right_wall,
+                    (0, 1) => !self.cells[idx].bottom_wall && !self.cells[beyond].top_wall,
+                    _ => !self.cells[idx].top_wall && !self.cells[beyond].bottom_wall,
+                };
+
+                if under && open {
+                    Some(beyond)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Breadth-first distances from the cell at `from` to every reachable cell.
+    pub fn distances(&self, from: usize) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.cell_count()];
+        let mut queue = VecDeque::from([from]);
+        distances[from] = Some(0);
+
+        while let Some(idx) = queue.pop_front() {
+            let distance = distances[idx].unwrap_or(0);
+            for next in self.passages(idx) {
+                if distances[next].is_none() {
+                    distances[next] = Some(distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// Shortest path from the center icon to the border icon, both ends included.
+    pub fn solve(&self) -> Option<Vec<usize>> {
+        let (start, exit) = (self.start?, self.exit?);
+        let distances = self.distances(start);
+        distances[exit]?;
+
+        // Walk back from the exit, always to a cell one step closer to the start.
+        let mut path = vec![exit];
+        let mut current = exit;
+        while current != start {
+            let distance = distances[current]?;
+            current = self
+                .passages(current)
+                .into_iter()
+                .find(|idx| distances[*idx] == Some(distance - 1))?;
+            path.push(current);
+        }
+        path.reverse();
+
+        Some(path)
+    }
+
+    /// How many different shortest paths lead from the center icon to the border icon.
+    /// Always one for a perfect maze, braiding can add more.
+    pub fn count_shortest_paths(&self) -> usize {
+        let (Some(start), Some(exit)) = (self.start, self.exit) else {
+            return 0;
+        };
+        let distances = self.distances(start);
+
+        // Visit the cells in order of distance and add up the ways to reach each of them.
+        let mut order: Vec<usize> = (0..distances.len())
+            .filter(|idx| distances[*idx].is_some())
+            .collect();
+        order.sort_by_key(|idx| distances[*idx]);
+
+        let mut counts = vec![0usize; distances.len()];
+        counts[start] = 1;
+        for idx in order {
+            for next in self.passages(idx) {
+                if distances[next] == distances[idx].map(|d| d + 1) {
+                    counts[next] = counts[next].saturating_add(counts[idx]);
+                }
+            }
+        }
+
+        counts[exit]
+    }
+}
@@ -0,0 +1,89 @@
+use std::f32::consts::TAU;
+
+use nannou::geom::Point2;
+use nannou::prelude::{pt2, vec2};
+
+use crate::models::Labyrinth;
+
+/// Points along a circle around the origin, one per degree, from `start` to `end` radians.
+pub fn arc(radius: f32, start: f32, end: f32) -> Vec<Point2> {
+    let steps = (end - start).abs().to_degrees().ceil().max(1.0) as usize;
+    (0..=steps)
+        .map(|i| {
+            let angle = start + (end - start) * i as f32 / steps as f32;
+            vec2(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+/// A classical labyrinth, drawn the way it has been drawn for thousands of years: first a
+/// seed of a cross, angles and dots, then arcs that connect the loose ends of the seed two by
+/// two. A seed makes 3, 7, 11, 15... circuits, `circuits` is rounded to the nearest of those.
+pub fn labyrinth(circuits: usize) -> Labyrinth {
+    // Angles nested in every quarter of the cross, and the arms of the cross
+    let n = ((circuits.max(3) - 3) as f32 / 4.0).round() as i32;
+    let m = n + 2;
+    let point = |x: i32, y: i32| pt2(x as f32, y as f32);
+
+    let mut walls = vec![
+        vec![point(0, -m), point(0, m)],
+        vec![point(-m, 0), point(m, 0)],
+    ];
+    for (x, y) in [(1, 1), (1, -1), (-1, -1), (-1, 1)] {
+        for k in 1..=n {
+            walls.push(vec![
+                point(x * k, y * m),
+                point(x * k, y * k),
+                point(x * m, y * k),
+            ]);
+        }
+        walls.push(vec![point(x * (n + 1), y * (n + 1))]);
+    }
+
+    // The loose ends of the seed, clockwise from the top of the cross. Every other quarter
+    // is the top right one turned.
+    let mut quarter = vec![];
+    quarter.extend((1..=n).map(|k| (k, m)));
+    quarter.push((n + 1, n + 1));
+    quarter.extend((1..=n).rev().map(|k| (m, k)));
+    quarter.push((m, 0));
+
+    let mut ends = vec![point(0, m)];
+    for turns in 0..4 {
+        ends.extend(quarter.iter().map(|&(mut x, mut y)| {
+            for _ in 0..turns {
+                (x, y) = (y, -x);
+            }
+            point(x, y)
+        }));
+    }
+    // Back at the top of the cross
+    ends.pop();
+
+    // The top of the cross to the first end on its right, then every end on the left to
+    // the next one on the right, each arc around the one before
+    let count = ends.len();
+    let first_radius = ((n * n + m * m) as f32).sqrt() + 1.0;
+    for i in 0..count / 2 {
+        let (left, right) = (ends[(count - i) % count], ends[i + 1]);
+        let radius = first_radius + i as f32;
+
+        let start = left.y.atan2(left.x);
+        let mut end = right.y.atan2(right.x);
+        while end >= start {
+            end -= TAU;
+        }
+
+        let mut wall = vec![left];
+        wall.extend(arc(radius, start, end));
+        wall.push(right);
+        walls.push(wall);
+    }
+
+    Labyrinth {
+        walls,
+        // Under the first arc, at the end of the path
+        goal: pt2(0.5, (m as f32 + first_radius) / 2.0),
+        goal_size: 0.8,
+    }
+}
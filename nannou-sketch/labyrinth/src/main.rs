@@ -0,0 +1,181 @@
+mod classical;
+mod medieval;
+mod models;
+
+use std::env;
+
+use bertools::shapes;
+use bertools::Record;
+use nannou::prelude::*;
+
+use models::truncate;
+use models::Kind;
+use models::Labyrinth;
+use models::Model;
+
+use bertools::do_save;
+use bertools::Nannou;
+
+/// Length of wall drawn per frame, in corridor widths.
+const SPEED: f32 = 1.0;
+/// Thickness of the walls, in corridor widths.
+const WALL_WEIGHT: f32 = 0.3;
+
+impl Default for Model {
+    fn default() -> Self {
+        let foreground_color = Hsla::new(336.0, 0.80, 0.47, 1.0);
+        let background_color = Hsla::new(40.0, 1.0, 0.57, 1.0);
+        let highlight_color = foreground_color;
+
+        Self {
+            seed: String::default(),
+            recorder: None,
+            background_color,
+            foreground_color,
+            highlight_color,
+            padding: 4.0,
+            kind: Kind::Classical,
+            circuits: Kind::Classical.default_circuits(),
+            labyrinth: Labyrinth {
+                walls: Vec::default(),
+                goal: pt2(0.0, 0.0),
+                goal_size: 1.0,
+            },
+            progress: 0.0,
+            finished: false,
+        }
+    }
+}
+
+fn main() {
+    nannou::app(model)
+        .update(update)
+        .loop_mode(LoopMode::default())
+        .run();
+}
+
+fn model(app: &App) -> Model {
+    let window_height = 900.0;
+    let window_width = 900.0;
+    let _window = app
+        .new_window()
+        .title("Find Love in a Labyrinth")
+        .size(window_height as u32, window_width as u32)
+        .event(event)
+        .view(view)
+        .build()
+        .unwrap();
+
+    let seed = env::var("SEED").unwrap_or_else(|_| "0".to_string());
+    // Classical (or cretan), chartres or square
+    let kind = env::var("KIND")
+        .ok()
+        .and_then(|kind| Kind::parse(&kind))
+        .unwrap_or(Kind::Classical);
+    let circuits = env::var("CIRCUITS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(kind.default_circuits());
+
+    let recorder = if std::env::var("RECORD").is_ok() {
+        Some(Record::new(app))
+    } else {
+        None
+    };
+    Model::new(seed, kind, circuits, recorder)
+}
+
+fn event(app: &App, model: &mut Model, event: WindowEvent) {
+    match event {
+        KeyPressed(Key::S) => do_save(app),
+        KeyPressed(Key::Escape) | Closed => {
+            if let Some(recorder) = &model.recorder {
+                recorder.finish();
+            }
+        }
+        _ => (),
+    }
+}
+
+fn update(_app: &App, model: &mut Model, _update: Update) {
+    model.update();
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    model.view(app, &draw);
+    draw.to_frame(app, &frame).unwrap();
+
+    // The video ends with the finished labyrinth
+    if let Some(recorder) = &model.recorder {
+        recorder.record(app);
+        if model.finished {
+            app.set_loop_mode(LoopMode::loop_ntimes(0));
+        }
+    }
+}
+
+impl Nannou for Model {
+    fn view(&self, app: &App, draw: &Draw) {
+        draw.background().color(self.background_color);
+
+        let window = app.window_rect();
+        let unit = self.unit(window.w(), window.h());
+
+        // From here on, everything is measured in corridor widths
+        let maze = draw.scale(unit);
+        let mut left = self.progress;
+        for wall in &self.labyrinth.walls {
+            if left <= 0.0 {
+                break;
+            }
+            if wall.len() == 1 {
+                maze.ellipse()
+                    .xy(wall[0])
+                    .radius(WALL_WEIGHT / 2.0)
+                    .color(self.foreground_color);
+                continue;
+            }
+            let drawn = truncate(wall, left);
+            left -= models::length(wall);
+            maze.polyline()
+                .weight(WALL_WEIGHT)
+                .caps_round()
+                .join_round()
+                .points(drawn)
+                .color(self.foreground_color);
+        }
+
+        if self.finished {
+            shapes::heart(
+                &maze,
+                self.labyrinth.goal,
+                self.labyrinth.goal_size,
+                self.highlight_color,
+            );
+        }
+
+        // Below the labyrinth, on the left. The text box is as wide as the window
+        let text_place = pt2(
+            unit * self.padding / 2.0,
+            -window.h() / 2.0 + unit * self.padding / 4.0,
+        );
+        draw.text(format!("{}  {:?} {}", self.seed, self.kind, self.circuits).as_str())
+            .xy(text_place)
+            .align_text_middle_y()
+            .left_justify()
+            .width(window.w())
+            .font_size(12)
+            .color(self.foreground_color);
+    }
+
+    fn update(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.progress += SPEED;
+        if self.progress >= self.total_length() {
+            self.finished = true;
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, TAU};
+
+use nannou::geom::Point2;
+use nannou::prelude::{pt2, vec2};
+use nannou::rand::rngs::StdRng;
+use nannou::rand::seq::SliceRandom;
+use nannou::rand::Rng;
+
+use crate::models::Labyrinth;
+
+/// Quarters of the labyrinth, with an axis between every two of them.
+const SECTORS: usize = 4;
+/// Radius of the open space in the middle, in corridor widths.
+const GOAL_RADIUS: f32 = 2.0;
+/// Swaps tried per circuit, to go from the plain labyrinth to a random one.
+const SHUFFLES: usize = 50;
+
+/// The two ends of a circuit in a quarter: on the axis it starts at, or the one it ends at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Side {
+    Low,
+    High,
+}
+
+impl Side {
+    fn other(self) -> Self {
+        match self {
+            Side::Low => Side::High,
+            Side::High => Side::Low,
+        }
+    }
+}
+
+/// Where the path turns and where it goes straight through the axes. A turn `(sector,
+/// side, k)` is a gap in circle k at that end of the quarter, from circuit k to k + 1. The
+/// entrance is a turn from circle 0, the outside, and the way into the middle one through
+/// the innermost circle. A passage `(axis, circuit)` goes straight through the axis, from
+/// the quarter before it into the one after.
+struct Paths {
+    circuits: usize,
+    turns: HashSet<(usize, Side, usize)>,
+    passages: HashSet<(usize, usize)>,
+}
+
+impl Paths {
+    /// Through the first circuit all the way around, back through the second one, and so on
+    /// into the middle. Every time around it turns at the bottom axis.
+    fn plain(circuits: usize) -> Self {
+        let turns = (0..=circuits)
+            .map(|k| match k % 2 {
+                0 => (0, Side::Low, k),
+                _ => (SECTORS - 1, Side::High, k),
+            })
+            .collect();
+        let passages = (1..SECTORS)
+            .flat_map(|axis| (1..=circuits).map(move |circuit| (axis, circuit)))
+            .collect();
+        Self {
+            circuits,
+            turns,
+            passages,
+        }
+    }
+
+    /// Whether there is a single path from the entrance to the middle, through every circuit
+    /// in every quarter. When it misses any, those go around in circles on their own.
+    fn unicursal(&self) -> bool {
+        let (mut circuit, mut sector, mut from) = (1, 0, Side::Low);
+        for _ in 1..self.circuits * SECTORS {
+            let to = from.other();
+            if circuit > 1 && self.turns.contains(&(sector, to, circuit - 1)) {
+                circuit -= 1;
+                from = to;
+            } else if circuit < self.circuits && self.turns.contains(&(sector, to, circuit)) {
+                circuit += 1;
+                from = to;
+            } else {
+                // Straight on, into the same side of the next quarter
+                let (axis, next) = match to {
+                    Side::Low => (sector, (sector + SECTORS - 1) % SECTORS),
+                    Side::High => ((sector + 1) % SECTORS, (sector + 1) % SECTORS),
+                };
+                if !self.passages.contains(&(axis, circuit)) {
+                    return false;
+                }
+                sector = next;
+            }
+        }
+        circuit == self.circuits && self.turns.contains(&(sector, from.other(), circuit))
+    }
+
+    /// Swap two passages next to each other through an axis for two turns on both sides
+    /// of it, or the other way around. Returns false when there is nothing to swap there.
+    fn swap(&mut self, axis: usize, circuit: usize) -> bool {
+        let passages = [(axis, circuit), (axis, circuit + 1)];
+        let turns = [
+            ((axis + SECTORS - 1) % SECTORS, Side::High, circuit),
+            (axis, Side::Low, circuit),
+        ];
+        if passages.iter().all(|p| self.passages.contains(p)) {
+            passages.iter().for_each(|p| {
+                self.passages.remove(p);
+            });
+            self.turns.extend(turns);
+        } else if turns.iter().all(|t| self.turns.contains(t)) {
+            turns.iter().for_each(|t| {
+                self.turns.remove(t);
+            });
+            self.passages.extend(passages);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Swap passages and turns at random, as long as the path stays a single one. A swap
+    /// mostly cuts a loop off the path, then a second swap has to join it back on.
+    fn shuffle(&mut self, rng: &mut StdRng) {
+        let mut places: Vec<(usize, usize)> = (0..SECTORS)
+            .flat_map(|axis| (1..self.circuits).map(move |circuit| (axis, circuit)))
+            .collect();
+        for _ in 0..SHUFFLES * self.circuits {
+            let (axis, circuit) = *places.choose(rng).unwrap();
+            if !self.swap(axis, circuit) || self.unicursal() {
+                continue;
+            }
+            places.shuffle(rng);
+            let joined = places.iter().any(|&(a, c)| {
+                if (a, c) == (axis, circuit) || !self.swap(a, c) {
+                    return false;
+                }
+                self.unicursal() || !self.swap(a, c)
+            });
+            if !joined {
+                self.swap(axis, circuit);
+            }
+        }
+    }
+
+    /// The same labyrinth in a mirror, entering from the other side of the bottom axis.
+    fn mirror(&self) -> Self {
+        Self {
+            circuits: self.circuits,
+            turns: self
+                .turns
+                .iter()
+                .map(|&(sector, side, k)| (SECTORS - 1 - sector, side.other(), k))
+                .collect(),
+            passages: self
+                .passages
+                .iter()
+                .map(|&(axis, circuit)| ((SECTORS - axis) % SECTORS, circuit))
+                .collect(),
+        }
+    }
+}
+
+/// A labyrinth in the style of the one in the floor of Chartres cathedral: every circuit is
+/// cut in four quarters, and the path turns back and forth between them. A different path
+/// for every seed. When `square`, the circuits are squares instead of circles.
+pub fn labyrinth(circuits: usize, square: bool, rng: &mut StdRng) -> Labyrinth {
+    let circuits = circuits.max(2);
+
+    let mut paths = Paths::plain(circuits);
+    paths.shuffle(rng);
+    if rng.gen_bool(0.5) {
+        paths = paths.mirror();
+    }
+    let Paths {
+        turns: gaps,
+        passages,
+        ..
+    } = paths;
+
+    let axis = |a: usize| -FRAC_PI_2 + a as f32 * TAU / SECTORS as f32;
+    let radius = |k: usize| GOAL_RADIUS + (circuits - k) as f32;
+    let shape = |point: Point2| {
+        if square {
+            // Out to the square around the circle
+            point / point.x.abs().max(point.y.abs()) * point.length()
+        } else {
+            point
+        }
+    };
+
+    // From the outside in, with a corridor wide gap where the path turns
+    let mut walls = vec![];
+    for k in 0..=circuits {
+        let radius = radius(k);
+        let gap = 1.0 / radius;
+        for sector in 0..SECTORS {
+            let mut start = axis(sector);
+            let mut end = axis(sector + 1);
+            if gaps.contains(&(sector, Side::Low, k)) {
+                start += gap;
+            }
+            if gaps.contains(&(sector, Side::High, k)) {
+                end -= gap;
+            }
+            walls.push(
+                ring(radius, start, end, square)
+                    .into_iter()
+                    .map(shape)
+                    .collect(),
+            );
+        }
+    }
+
+    // Along the axes, everywhere the path doesn't go straight through
+    for a in 0..SECTORS {
+        let direction = vec2(axis(a).cos(), axis(a).sin());
+        let mut circuit = 1;
+        while circuit <= circuits {
+            if passages.contains(&(a, circuit)) {
+                circuit += 1;
+                continue;
+            }
+            let outer = circuit;
+            while circuit < circuits && !passages.contains(&(a, circuit + 1)) {
+                circuit += 1;
+            }
+            walls.push(vec![
+                shape(direction * radius(outer - 1)),
+                shape(direction * radius(circuit)),
+            ]);
+            circuit += 1;
+        }
+    }
+
+    Labyrinth {
+        walls,
+        goal: pt2(0.0, 0.0),
+        goal_size: GOAL_RADIUS,
+    }
+}
+
+/// Points along a circle from `start` to `end`, one per degree. The corners of the square
+/// around it are in there as well, so the walls of a square labyrinth keep sharp corners.
+fn ring(radius: f32, start: f32, end: f32, square: bool) -> Vec<Point2> {
+    let steps = (end - start).to_degrees().ceil().max(1.0) as usize;
+    let mut angles: Vec<f32> = (0..=steps)
+        .map(|i| start + (end - start) * i as f32 / steps as f32)
+        .collect();
+    if square {
+        angles.extend(
+            (-4..8)
+                .map(|corner| FRAC_PI_4 + corner as f32 * FRAC_PI_2)
+                .filter(|angle| (start..end).contains(angle)),
+        );
+        angles.sort_by(f32::total_cmp);
+    }
+    angles
+        .into_iter()
+        .map(|angle| vec2(angle.cos(), angle.sin()) * radius)
+        .collect()
+}
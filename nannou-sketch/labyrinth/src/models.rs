@@ -0,0 +1,141 @@
+use std::hash::{Hash, Hasher};
+
+use bertools::schemes::seeded_colors;
+use bertools::Record;
+use nannou::color::Hsla;
+use nannou::geom::Point2;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::SeedableRng;
+
+use crate::classical;
+use crate::medieval;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// Cretan: arcs drawn around a seed of a cross, angles and dots.
+    Classical,
+    /// Chartres: circuits cut into four quarters, turning back at the axes.
+    Chartres,
+    /// The quarters of Chartres, on square circuits.
+    Square,
+}
+
+impl Kind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "classical" | "cretan" => Some(Kind::Classical),
+            "chartres" => Some(Kind::Chartres),
+            "square" => Some(Kind::Square),
+            _ => None,
+        }
+    }
+
+    pub fn default_circuits(&self) -> usize {
+        match self {
+            Kind::Classical => 7,
+            Kind::Chartres => 11,
+            Kind::Square => 7,
+        }
+    }
+}
+
+/// The walls of a labyrinth, in corridor widths. The path between them has no branches, it
+/// winds through every circuit before it reaches the goal.
+pub struct Labyrinth {
+    /// In the order they are drawn. A wall of a single point is a dot.
+    pub walls: Vec<Vec<Point2>>,
+    /// Where the heart goes, at the end of the path.
+    pub goal: Point2,
+    pub goal_size: f32,
+}
+
+pub struct Model {
+    pub seed: String,
+    pub recorder: Option<Record>,
+    pub background_color: Hsla,
+    pub foreground_color: Hsla,
+    pub highlight_color: Hsla,
+    pub padding: f32,
+    pub kind: Kind,
+    pub circuits: usize,
+    pub labyrinth: Labyrinth,
+    /// Length of the walls drawn so far, they are drawn one after the other.
+    pub progress: f32,
+    pub finished: bool,
+}
+
+impl Model {
+    pub fn new(seed: String, kind: Kind, circuits: usize, recorder: Option<Record>) -> Self {
+        let default = Self::default();
+
+        // Convert seed String to a u64
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        seed.hash(&mut hasher);
+        let seed_n = hasher.finish();
+
+        let mut rng = StdRng::seed_from_u64(seed_n);
+        let (foreground_color, background_color) =
+            seeded_colors(&mut rng, default.foreground_color, default.background_color);
+
+        let labyrinth = match kind {
+            Kind::Classical => classical::labyrinth(circuits),
+            Kind::Chartres => medieval::labyrinth(circuits, false, &mut rng),
+            Kind::Square => medieval::labyrinth(circuits, true, &mut rng),
+        };
+
+        Self {
+            seed,
+            recorder,
+            foreground_color,
+            background_color,
+            highlight_color: foreground_color,
+            kind,
+            circuits,
+            labyrinth,
+            ..default
+        }
+    }
+
+    /// Size of a corridor in pixels, when the labyrinth and its padding fit in `width` by
+    /// `height` pixels.
+    pub fn unit(&self, width: f32, height: f32) -> f32 {
+        let extent = self
+            .labyrinth
+            .walls
+            .iter()
+            .flatten()
+            .map(|point| point.x.abs().max(point.y.abs()))
+            .fold(0.0, f32::max);
+        width.min(height) / (2.0 * extent + self.padding)
+    }
+
+    pub fn total_length(&self) -> f32 {
+        self.labyrinth.walls.iter().map(|wall| length(wall)).sum()
+    }
+}
+
+pub fn length(points: &[Point2]) -> f32 {
+    points
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .sum()
+}
+
+/// The first `length` of a line.
+pub fn truncate(points: &[Point2], length: f32) -> Vec<Point2> {
+    let mut left = length;
+    let mut truncated = points[..1].to_vec();
+    for pair in points.windows(2) {
+        if left <= 0.0 {
+            break;
+        }
+        let step = pair[0].distance(pair[1]);
+        if step >= left {
+            truncated.push(pair[0].lerp(pair[1], left / step));
+            break;
+        }
+        left -= step;
+        truncated.push(pair[1]);
+    }
+    truncated
+}
@@ -0,0 +1,335 @@
+use std::f32::consts::SQRT_2;
+use std::fs;
+use std::io;
+
+use bertools::saves_location;
+use nannou::geom::Point2;
+use nannou::prelude::*;
+
+use crate::models::{Model, Petal, Ring};
+
+/// Line weight of a colouring page, in millimetres.
+const LINE_WEIGHT: f32 = 0.5;
+/// Room around the mandala on a colouring page, in millimetres.
+const MARGIN: f32 = 15.0;
+/// Steps of halving the distance to where a line goes under another shape.
+const BISECTIONS: usize = 10;
+/// Pixels between the points of the outlines on screen, and while morphing, when they are
+/// made again every frame.
+const SCREEN_STEP: f32 = 1.0;
+const MORPH_STEP: f32 = 4.0;
+
+/// A closed outline of something that is drawn filled. Shapes in the same group are merged
+/// into one, as they have the same colour.
+struct Shape {
+    outline: Vec<Point2>,
+    group: usize,
+    min: Point2,
+    max: Point2,
+}
+
+impl Shape {
+    fn new(outline: Vec<Point2>, group: usize) -> Self {
+        let min = outline
+            .iter()
+            .fold(outline[0], |min, point| min.min(*point));
+        let max = outline
+            .iter()
+            .fold(outline[0], |max, point| max.max(*point));
+        Self {
+            outline,
+            group,
+            min,
+            max,
+        }
+    }
+
+    fn contains(&self, point: Point2) -> bool {
+        if point.x < self.min.x || point.y < self.min.y {
+            return false;
+        }
+        if point.x > self.max.x || point.y > self.max.y {
+            return false;
+        }
+        // Count how often a line from the point to the right crosses the outline
+        let count = self.outline.len();
+        (0..count)
+            .filter(|&i| {
+                let (a, b) = (self.outline[i], self.outline[(i + 1) % count]);
+                (a.y > point.y) != (b.y > point.y)
+                    && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+            })
+            .count()
+            % 2
+            == 1
+    }
+}
+
+impl Ring {
+    /// The outlines of `petals`, turned into place like the petals of the ring.
+    fn outlines(&self, petals: &[Petal]) -> Vec<Vec<Point2>> {
+        let step = 360.0 / self.petal_count as f32;
+        petals
+            .iter()
+            .enumerate()
+            .map(|(i, petal)| {
+                let angle = deg_to_rad(self.rotation + step * i as f32);
+                petal
+                    .outline()
+                    .into_iter()
+                    .map(|point| turn(point, angle))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Turned counter-clockwise around the origin, by `angle` radians.
+fn turn(point: Point2, angle: f32) -> Point2 {
+    let (sin, cos) = angle.sin_cos();
+    pt2(point.x * cos - point.y * sin, point.x * sin + point.y * cos)
+}
+
+/// Points at most `step` apart along a closed outline, back to where it started.
+fn resample(outline: &[Point2], step: f32) -> Vec<Point2> {
+    let mut points = vec![];
+    for i in 0..outline.len() {
+        let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+        let steps = (a.distance(b) / step).ceil().max(1.0) as usize;
+        points.extend((0..steps).map(|s| a.lerp(b, s as f32 / steps as f32)));
+    }
+    points.push(outline[0]);
+    points
+}
+
+impl Model {
+    /// Everything the mandala fills, in the order it is drawn: the rings from the outside
+    /// in, each petal followed by the petal nested in it, then the centerpiece.
+    fn shapes(&self) -> Vec<Shape> {
+        let rings = self.shown_rings();
+        let mut shapes = vec![];
+        for (i, ring) in rings.iter().rev().enumerate() {
+            let mut nested = ring.outlines(&ring.nested).into_iter();
+            for petal in ring.outlines(&ring.petals) {
+                shapes.push(Shape::new(petal, 2 * i));
+                if let Some(outline) = nested.next() {
+                    shapes.push(Shape::new(outline, 2 * i + 1));
+                }
+            }
+        }
+
+        let half = self.centerpiece.radius / 2.0;
+        let square = vec![
+            pt2(-half, -half),
+            pt2(half, -half),
+            pt2(half, half),
+            pt2(-half, half),
+        ];
+        let circle = (0..360)
+            .map(|degrees| turn(pt2(half, 0.0), deg_to_rad(degrees as f32)))
+            .collect();
+        let group = 2 * rings.len();
+        shapes.push(Shape::new(square, group));
+        shapes.push(Shape::new(circle, group + 1));
+        shapes
+    }
+
+    /// The lines of a colouring page: the outlines of everything the mandala fills, without
+    /// the parts that are covered by what is drawn on top, or that are inside shapes they
+    /// merge with. What is left are closed regions, each of them one colour in the mandala.
+    /// Lines are at most `step` apart.
+    pub fn outlines(&self, step: f32) -> Vec<Vec<Point2>> {
+        let shapes = self.shapes();
+        let mut lines = vec![];
+
+        for (i, shape) in shapes.iter().enumerate() {
+            let visible = |point: Point2| {
+                !shapes.iter().enumerate().any(|(j, other)| {
+                    j != i && (j > i || other.group == shape.group) && other.contains(point)
+                })
+            };
+            // Where a line goes under another shape, between a visible and a hidden point
+            let edge = |mut shown: Point2, mut hidden: Point2| {
+                for _ in 0..BISECTIONS {
+                    let middle = shown.lerp(hidden, 0.5);
+                    if visible(middle) {
+                        shown = middle;
+                    } else {
+                        hidden = middle;
+                    }
+                }
+                shown
+            };
+
+            let points = resample(&shape.outline, step);
+            let shown: Vec<bool> = points.iter().map(|point| visible(*point)).collect();
+            if shown.iter().all(|shown| *shown) {
+                lines.push(points);
+                continue;
+            }
+
+            // Start at a hidden point, so every visible run is in one piece
+            let start = shown.iter().position(|shown| !*shown).unwrap();
+            let count = points.len() - 1;
+            let mut line: Vec<Point2> = vec![];
+            for k in 1..=count {
+                let (previous, current) = ((start + k - 1) % count, (start + k) % count);
+                match (shown[previous], shown[current]) {
+                    (false, true) => {
+                        line = vec![edge(points[current], points[previous]), points[current]]
+                    }
+                    (true, true) => line.push(points[current]),
+                    (true, false) => {
+                        line.push(edge(points[previous], points[current]));
+                        lines.push(std::mem::take(&mut line));
+                    }
+                    (false, false) => (),
+                }
+            }
+        }
+        lines
+    }
+
+    /// The mandala in the middle of a page of `width` by `height` millimetres: the scale
+    /// from pixels to millimetres, and where the centre goes.
+    fn page(&self, width: f32, height: f32) -> (f32, Point2) {
+        let extent = self
+            .shown_rings()
+            .iter()
+            .map(|ring| ring.outer_radius)
+            .fold(self.centerpiece.radius / 2.0 * SQRT_2, f32::max);
+        let scale = (width.min(height) / 2.0 - MARGIN) / extent;
+        (scale, pt2(width / 2.0, height / 2.0))
+    }
+
+    /// A colouring page of `width` by `height` millimetres, as SVG.
+    pub fn svg(&self, width: f32, height: f32) -> String {
+        let (scale, center) = self.page(width, height);
+
+        let mut svg = vec![
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="0 0 {w} {h}">"#,
+                w = width,
+                h = height
+            ),
+            format!(
+                r#"<g fill="none" stroke="black" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round">"#,
+                LINE_WEIGHT
+            ),
+        ];
+        for line in self.outlines(LINE_WEIGHT / scale) {
+            // SVG goes down where the mandala goes up
+            let points: Vec<String> = line
+                .iter()
+                .map(|point| {
+                    let point = center + pt2(point.x, -point.y) * scale;
+                    format!("{:.2},{:.2}", point.x, point.y)
+                })
+                .collect();
+            svg.push(format!(r#"<polyline points="{}"/>"#, points.join(" ")));
+        }
+        svg.push("</g>".to_string());
+        svg.push("</svg>".to_string());
+        svg.join("\n") + "\n"
+    }
+
+    /// A colouring page of `width` by `height` millimetres, as a PDF of one page.
+    pub fn pdf(&self, width: f32, height: f32) -> String {
+        // PDF measures in points, 72 to the inch
+        let points = |mm: f32| mm / 25.4 * 72.0;
+        let (scale, center) = self.page(width, height);
+
+        let mut content = vec![format!("{:.2} w 1 J 1 j", points(LINE_WEIGHT))];
+        for line in self.outlines(LINE_WEIGHT / scale) {
+            let mut path: Vec<String> = line
+                .iter()
+                .map(|point| {
+                    let point = center + *point * scale;
+                    format!("{:.2} {:.2}", points(point.x), points(point.y))
+                })
+                .collect();
+            path[0].push_str(" m");
+            path[1..].iter_mut().for_each(|point| point.push_str(" l"));
+            content.push(path.join(" ") + " S");
+        }
+        let content = content.join("\n");
+
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << >> /Contents 4 0 R >>",
+                points(width),
+                points(height)
+            ),
+            format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                content.len(),
+                content
+            ),
+        ];
+
+        let mut pdf = "%PDF-1.4\n".to_string();
+        let mut offsets = vec![];
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+        }
+        let xref = pdf.len();
+        pdf.push_str(&format!(
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        ));
+        for offset in offsets {
+            pdf.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        pdf.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        ));
+        pdf
+    }
+
+    /// Write the colouring page to the saves folder as SVG and PDF, named after the seed.
+    pub fn export_coloring_page(
+        &self,
+        name: &str,
+        width: f32,
+        height: f32,
+    ) -> io::Result<Vec<String>> {
+        let mut file_names = vec![];
+        for (extension, contents) in [
+            ("svg", self.svg(width, height)),
+            ("pdf", self.pdf(width, height)),
+        ] {
+            let file_name = format!("{}{}-{}.{}", saves_location(), name, self.seed, extension);
+            fs::write(&file_name, contents)?;
+            file_names.push(file_name);
+        }
+        Ok(file_names)
+    }
+
+    /// Makes the outlines on screen, if they are not there yet.
+    pub fn refresh_outlines(&mut self) {
+        if self.lines.is_none() {
+            let step = match self.morph {
+                Some(_) => MORPH_STEP,
+                None => SCREEN_STEP,
+            };
+            self.lines = Some(self.outlines(step));
+        }
+    }
+
+    /// The outlines on screen, in the foreground colour.
+    pub fn view_outlines(&self, draw: &Draw) {
+        for line in self.lines.iter().flatten() {
+            draw.polyline()
+                .weight(2.0)
+                .caps_round()
+                .join_round()
+                .points(line.iter().copied())
+                .color(self.foreground_color);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::fs;
+use std::io;
+
+use bertools::saves_location;
+use nannou::color::Hsla;
+use nannou::geom::Point2;
+use nannou::prelude::*;
+
+use crate::models::Model;
+
+/// Points closer to the previous one than this, in pixels, are left out of a stroke.
+const MIN_STEP: f32 = 2.0;
+/// Rounds of smoothing, each one cuts every corner of a stroke in two.
+const SMOOTHING: usize = 3;
+
+/// A line drawn with the mouse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    pub points: Vec<Point2>,
+    pub weight: f32,
+    pub color: Hsla,
+}
+
+/// Strokes drawn by hand, repeated around the centre with the symmetry of the mandala.
+pub struct Drawing {
+    pub strokes: Vec<Stroke>,
+    /// Undone strokes, the last one undone at the end.
+    pub undone: Vec<Stroke>,
+    /// While the mouse button is down.
+    pub current: Option<Stroke>,
+    /// Each stroke mirrored as well as turned.
+    pub mirror: bool,
+    pub weight: f32,
+    /// Of the palette of the mandala.
+    pub color: usize,
+}
+
+impl Default for Drawing {
+    fn default() -> Self {
+        Self {
+            strokes: vec![],
+            undone: vec![],
+            current: None,
+            mirror: true,
+            weight: 4.0,
+            color: 0,
+        }
+    }
+}
+
+impl Drawing {
+    pub fn begin(&mut self, point: Point2, color: Hsla) {
+        self.current = Some(Stroke {
+            points: vec![point],
+            weight: self.weight,
+            color,
+        });
+    }
+
+    pub fn extend(&mut self, point: Point2) {
+        if let Some(stroke) = &mut self.current {
+            if stroke.points.last().unwrap().distance(point) >= MIN_STEP {
+                stroke.points.push(point);
+            }
+        }
+    }
+
+    /// A new stroke can't be redone over.
+    pub fn end(&mut self) {
+        if let Some(stroke) = self.current.take() {
+            self.strokes.push(stroke);
+            self.undone.clear();
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(stroke) = self.strokes.pop() {
+            self.undone.push(stroke);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(stroke) = self.undone.pop() {
+            self.strokes.push(stroke);
+        }
+    }
+
+    pub fn thicker(&mut self) {
+        self.weight += 1.0;
+    }
+
+    pub fn thinner(&mut self) {
+        self.weight = (self.weight - 1.0).max(1.0);
+    }
+}
+
+impl Stroke {
+    fn line(&self) -> String {
+        let points: Vec<String> = self
+            .points
+            .iter()
+            .map(|point| format!("{:.1},{:.1}", point.x, point.y))
+            .collect();
+        format!(
+            "stroke {} {} {} {} {} {}",
+            self.weight,
+            self.color.hue.to_positive_degrees(),
+            self.color.saturation,
+            self.color.lightness,
+            self.color.alpha,
+            points.join(" ")
+        )
+    }
+
+    /// A line like `stroke 4 342 0.6 0.45 1 10.0,20.0 12.5,22.0`: the weight, the colour
+    /// as hue, saturation, lightness and alpha, and the points.
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.strip_prefix("stroke ")?.split_whitespace();
+        let mut number = || -> Option<f32> { words.next()?.parse().ok() };
+        let weight = number()?;
+        let color = Hsla::new(number()?, number()?, number()?, number()?);
+        let points = words
+            .map(|point| {
+                let (x, y) = point.split_once(',')?;
+                Some(pt2(x.parse().ok()?, y.parse().ok()?))
+            })
+            .collect::<Option<Vec<Point2>>>()?;
+        if points.is_empty() {
+            return None;
+        }
+        Some(Self {
+            points,
+            weight,
+            color,
+        })
+    }
+}
+
+/// Round off the corners of a line, keeping its ends where they are.
+pub fn smooth(points: &[Point2]) -> Vec<Point2> {
+    let mut points = points.to_vec();
+    for _ in 0..SMOOTHING {
+        if points.len() < 3 {
+            break;
+        }
+        let mut smoothed = vec![points[0]];
+        for pair in points.windows(2) {
+            smoothed.push(pair[0].lerp(pair[1], 0.25));
+            smoothed.push(pair[0].lerp(pair[1], 0.75));
+        }
+        smoothed.push(points[points.len() - 1]);
+        points = smoothed;
+    }
+    points
+}
+
+impl Model {
+    /// The strokes, turned around the centre `symmetry` times, and mirrored when the
+    /// drawing is. Faint guides show where the copies go.
+    pub fn view_drawing(&self, draw: &Draw) {
+        let angle = TAU / self.symmetry as f32;
+        let guide = Hsla {
+            alpha: 0.1,
+            ..self.foreground_color
+        };
+        (0..self.symmetry).for_each(|i| {
+            draw.rotate(angle * i as f32)
+                .line()
+                .start(pt2(0., 0.))
+                .end(pt2(1000., 0.))
+                .weight(1.0)
+                .color(guide);
+        });
+
+        let strokes: Vec<(Vec<Point2>, &Stroke)> = self
+            .drawing
+            .strokes
+            .iter()
+            .chain(&self.drawing.current)
+            .map(|stroke| (smooth(&stroke.points), stroke))
+            .collect();
+
+        (0..self.symmetry).for_each(|i| {
+            let draw = draw.rotate(angle * i as f32);
+            let mut copies = vec![draw.clone()];
+            if self.drawing.mirror {
+                copies.push(draw.scale_y(-1.0));
+            }
+            for draw in copies {
+                for (points, stroke) in &strokes {
+                    if points.len() == 1 {
+                        draw.ellipse()
+                            .xy(points[0])
+                            .radius(stroke.weight / 2.0)
+                            .color(stroke.color);
+                        continue;
+                    }
+                    draw.polyline()
+                        .weight(stroke.weight)
+                        .caps_round()
+                        .join_round()
+                        .points(points.clone())
+                        .color(stroke.color);
+                }
+            }
+        });
+    }
+
+    /// Write the strokes to the saves folder, to open again with `open_drawing`.
+    pub fn save_drawing(&self, name: &str) -> io::Result<String> {
+        let mut lines = vec![
+            format!("symmetry {}", self.symmetry),
+            format!("mirror {}", self.drawing.mirror),
+        ];
+        lines.extend(self.drawing.strokes.iter().map(Stroke::line));
+
+        let file_name = format!("{}{}-{}{}", saves_location(), name, self.seed, ".strokes");
+        fs::write(&file_name, lines.join("\n") + "\n")?;
+        Ok(file_name)
+    }
+
+    /// Open saved strokes to draw on.
+    pub fn open_drawing(&mut self, path: &str) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        let mut drawing = Drawing::default();
+        for line in text.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "symmetry" => self.symmetry = value.parse().unwrap_or(self.symmetry).max(3),
+                "mirror" => drawing.mirror = value == "true",
+                _ => match Stroke::parse(line) {
+                    Some(stroke) => drawing.strokes.push(stroke),
+                    None => println!("Can't read the stroke {:?}", line),
+                },
+            }
+        }
+        self.drawing = drawing;
+        Ok(())
+    }
+}
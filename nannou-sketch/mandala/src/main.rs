@@ -1,18 +1,29 @@
+use std::env;
+
 use models::Centerpiece;
+use models::Mode;
 use models::Petal;
+use models::Ring;
 use nannou::geom;
 use nannou::prelude::*;
 use nannou::rand::seq::SliceRandom;
 use nannou::rand::thread_rng;
 
+mod coloring;
+mod drawing;
 mod models;
+mod morph;
+mod petals;
+use crate::drawing::Drawing;
 use crate::models::Model;
+use crate::morph::Morph;
 use bertools::do_save;
+use bertools::print::paper_size;
 use bertools::Nannou;
+use bertools::Record;
 
 impl Default for Model {
     fn default() -> Self {
-        let angle = 15.0;
         let mut offsets = vec![
             random_range(0.0, 0.2),
             random_range(0.2, 0.4),
@@ -22,16 +33,21 @@ impl Default for Model {
         offsets.shuffle(&mut thread_rng());
 
         let centerpiece = Centerpiece::default();
-        // let petals = Petal::generate(20);
-        let petals = Petal::generate(360 / (2 * angle as usize));
 
         Self {
+            seed: String::default(),
             background_color: hsla(0.0, 0.0, 1.0, 1.0),
             foreground_color: hsla(0.0, 0.0, 0.0, 1.0),
-            angle,
+            palette: vec![hsla(0.0, 0.0, 0.0, 1.0)],
+            mode: Mode::Petals,
+            symmetry: 12,
             offsets,
             centerpiece,
-            petals,
+            rings: vec![],
+            morph: None,
+            lines: None,
+            drawing: Drawing::default(),
+            recorder: None,
         }
     }
 }
@@ -54,6 +70,7 @@ impl Default for Petal {
             ctrl1: zero,
             ctrl2: zero,
             end: zero,
+            half: vec![],
             background_color: hsla(0.0, 0.0, 1.0, 0.6),
             foreground_color: hsla(0.0, 0.0, 0.0, 0.6),
         }
@@ -77,24 +94,169 @@ fn model(app: &App) -> Model {
         .build()
         .unwrap();
 
-    Model::default()
+    // A new mandala every time, unless there's a seed to get one back
+    let seed = env::var("SEED").unwrap_or_else(|_| random::<u32>().to_string());
+    // Valentine, flatgreen or navy
+    let palette = env::var("PALETTE")
+        .ok()
+        .and_then(|name| models::palette(&name))
+        .unwrap_or_else(|| models::palette("valentine").unwrap());
+    // A number of rings, or rings like 24@40-120,12@120-220/15
+    let rings = env::var("RINGS").unwrap_or_default();
+
+    let mut model = Model::new(seed, palette, &rings);
+    println!("Seed {}", model.seed);
+
+    // Petals or kaleidoscope. Press M to switch
+    model.mode = env::var("MODE")
+        .ok()
+        .and_then(|mode| Mode::parse(&mode))
+        .unwrap_or(Mode::Petals);
+    // How many times the kaleidoscope repeats its slice. Up and Down to change it
+    model.symmetry = env::var("SYMMETRY")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(model.symmetry)
+        .max(3);
+
+    model.morph = morph(&model);
+
+    // Strokes saved with E, to draw on some more
+    if let Ok(path) = env::var("DRAWING") {
+        match model.open_drawing(&path) {
+            Ok(()) => model.mode = Mode::Drawing,
+            Err(e) => println!("Error opening drawing: {:?}", e),
+        }
+    }
+    if std::env::var("RECORD").is_ok() {
+        model.recorder = Some(Record::new(app));
+    }
+
+    // Colouring pages of many mandalas at once: the seeds, like 3,14,15, or a number of
+    // seeds counting from 1
+    if let Ok(batch) = env::var("BATCH") {
+        let seeds: Vec<String> = match batch.trim().parse::<usize>() {
+            Ok(count) => (1..=count).map(|i| i.to_string()).collect(),
+            Err(_) => batch
+                .split(',')
+                .map(|seed| seed.trim().to_string())
+                .collect(),
+        };
+        for seed in seeds {
+            let page = Model::new(seed, model.palette.clone(), &rings);
+            do_coloring_page(app, &page);
+        }
+        app.quit();
+    }
+    model
+}
+
+/// Save the mandala as a colouring page, on paper of the size in PAGE, like A4.
+fn do_coloring_page(app: &App, model: &Model) {
+    let paper = env::var("PAGE").unwrap_or_else(|_| "A4".to_string());
+    let Some((width, height)) = paper_size(&paper) else {
+        println!("Unknown paper size {:?}", paper);
+        return;
+    };
+    match model.export_coloring_page(&app.exe_name().unwrap(), width, height) {
+        Ok(file_names) => file_names
+            .iter()
+            .for_each(|file_name| println!("Saved to file://{}", file_name)),
+        Err(e) => println!("Error saving colouring page: {:?}", e),
+    }
+}
+
+/// With MORPH, the mandala morphs into the mandalas of other seeds and back in a loop of
+/// FRAMES frames. MORPH lists the seeds, like 3,14,15, or is the number of seeds to make up.
+fn morph(model: &Model) -> Option<Morph> {
+    let morph = env::var("MORPH").ok()?;
+    let seeds: Vec<String> = match morph.trim().parse::<usize>() {
+        Ok(count) => (1..=count)
+            .map(|i| format!("{}-{}", model.seed, i))
+            .collect(),
+        Err(_) => morph
+            .split(',')
+            .map(|seed| seed.trim().to_string())
+            .collect(),
+    };
+    let frames = env::var("FRAMES")
+        .ok()
+        .and_then(|f| f.parse().ok())
+        .unwrap_or(240);
+    let rings = env::var("RINGS").unwrap_or_default();
+    Some(Morph::new(model, &seeds, &rings, frames))
 }
 
 fn event(app: &App, model: &mut Model, event: Event) {
     match event {
         Event::WindowEvent { id: _id, simple } => {
+            if model.mode == Mode::Drawing {
+                draw_event(app, model, &simple);
+            }
             if let Some(KeyPressed(Key::S)) = simple {
                 do_save(app);
             }
             if let Some(KeyPressed(Key::R)) = simple {
                 do_shuffle(model);
             }
+            if let Some(KeyPressed(Key::P)) = simple {
+                do_coloring_page(app, model);
+            }
+            if let Some(KeyPressed(Key::M)) = simple {
+                model.mode = model.mode.next();
+            }
+            if let Some(KeyPressed(Key::Up)) = simple {
+                model.symmetry += 1;
+                model.lines = None;
+            }
+            if let Some(KeyPressed(Key::Down)) = simple {
+                model.symmetry = (model.symmetry - 1).max(3);
+                model.lines = None;
+            }
+            if let Some(KeyPressed(Key::Escape)) | Some(Closed) = simple {
+                if let Some(recorder) = &model.recorder {
+                    recorder.finish();
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Draw with the mouse. Z undoes a stroke and Y redoes it, [ and ] make the next stroke
+/// thinner or thicker, C picks the next colour of the palette, X mirrors the strokes or
+/// stops mirroring them, and E saves the strokes.
+fn draw_event(app: &App, model: &mut Model, event: &Option<WindowEvent>) {
+    match event {
+        Some(MousePressed(MouseButton::Left)) => {
+            let color = model.palette[model.drawing.color % model.palette.len()];
+            model.drawing.begin(app.mouse.position(), color);
         }
+        Some(MouseMoved(position)) => model.drawing.extend(*position),
+        Some(MouseReleased(MouseButton::Left)) => model.drawing.end(),
+        Some(KeyPressed(Key::Z)) => model.drawing.undo(),
+        Some(KeyPressed(Key::Y)) => model.drawing.redo(),
+        Some(KeyPressed(Key::LBracket)) => model.drawing.thinner(),
+        Some(KeyPressed(Key::RBracket)) => model.drawing.thicker(),
+        Some(KeyPressed(Key::C)) => model.drawing.color += 1,
+        Some(KeyPressed(Key::X)) => model.drawing.mirror = !model.drawing.mirror,
+        Some(KeyPressed(Key::E)) => match model.save_drawing(&app.exe_name().unwrap()) {
+            Ok(file_name) => println!("Saved to file://{}", file_name),
+            Err(e) => println!("Error saving drawing: {:?}", e),
+        },
         _ => (),
     }
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
+fn update(app: &App, model: &mut Model, _update: Update) {
+    // Counted from the start, so a recording starts at the first frame of the loop
+    if let Some(morph) = &mut model.morph {
+        morph.frame = app.elapsed_frames() as usize % morph.frames;
+        model.lines = None;
+    }
+    if model.mode == Mode::Outline {
+        model.refresh_outlines();
+    }
     model.update();
 }
 
@@ -102,85 +264,51 @@ fn view(app: &App, model: &Model, frame: Frame) {
     let draw = app.draw();
     model.view(app, &draw);
     draw.to_frame(app, &frame).unwrap();
+
+    // A morph is recorded exactly once around, so the video loops without a seam
+    if let Some(recorder) = &model.recorder {
+        recorder.record(app);
+        if let Some(morph) = &model.morph {
+            if app.elapsed_frames() as usize + 1 >= morph.frames {
+                app.set_loop_mode(LoopMode::loop_ntimes(0));
+            }
+        }
+    }
 }
 
 fn do_shuffle(model: &mut Model) {
     model.offsets.shuffle(&mut thread_rng());
 
-    model.petals = Petal::generate(360 / (2 * model.angle as usize));
+    // Another seed, the same rings if they were given
+    let seed = random::<u32>().to_string();
+    let rings = env::var("RINGS").unwrap_or_default();
+    *model = Model {
+        mode: model.mode,
+        symmetry: model.symmetry,
+        drawing: std::mem::take(&mut model.drawing),
+        recorder: model.recorder.take(),
+        ..Model::new(seed, model.palette.clone(), &rings)
+    };
+    model.morph = morph(model);
+    println!("Seed {}", model.seed);
 }
 
 impl Nannou for Model {
     fn view(&self, app: &App, draw: &Draw) {
         draw.background().color(self.background_color);
 
-        // let height = app.window_rect().h() / 2.0;
-        // let width = app.window_rect().w() / 2.0;
-        // let size = height.min(width);
-        // draw.ellipse()
-        //     .color(self.background_color)
-        //     .w_h(width, height)
-        //     .x_y(0., 0.);
-        //
-        // // Draw the slice
-        // let slice = self.slice(pt2(0., 0.), self.angle, size);
-        //
-        // // mirror the slice
-        // let mirrored_slice = slice
-        //     .into_iter()
-        //     .map(|evt| match evt {
-        //         lyon::path::Event::Begin { at } => lyon::path::Event::Begin {
-        //             at: pt2(at.x, -at.y).to_array().into(),
-        //         },
-        //         lyon::path::Event::Line { from, to } => lyon::path::Event::Line {
-        //             from: pt2(from.x, -from.y).to_array().into(),
-        //             to: pt2(to.x, -to.y).to_array().into(),
-        //         },
-        //         lyon::path::Event::Cubic {
-        //             from,
-        //             ctrl1,
-        //             ctrl2,
-        //             to,
-        //         } => lyon::path::Event::Cubic {
-        //             from: pt2(from.x, -from.y).to_array().into(),
-        //             ctrl1: pt2(ctrl1.x, -ctrl1.y).to_array().into(),
-        //             ctrl2: pt2(ctrl2.x, -ctrl2.y).to_array().into(),
-        //             to: pt2(to.x, -to.y).to_array().into(),
-        //         },
-        //         lyon::path::Event::End { last, first, close } => lyon::path::Event::End {
-        //             last: pt2(last.x, -last.y).to_array().into(),
-        //             first: pt2(first.x, -first.y).to_array().into(),
-        //             close,
-        //         },
-        //         lyon::path::Event::Quadratic { from, ctrl, to } => lyon::path::Event::Quadratic {
-        //             from: pt2(from.x, -from.y).to_array().into(),
-        //             ctrl: pt2(ctrl.x, -ctrl.y).to_array().into(),
-        //             to: pt2(to.x, -to.y).to_array().into(),
-        //         },
-        //     })
-        //     .collect::<Vec<_>>();
-        //
-        // let _ = (0..(360. / self.angle * 2.) as usize)
-        //     .map(|i| {
-        //         let draw = draw.rotate(deg_to_rad(i as f32 * self.angle * 2.));
-        //
-        //         // let draw = draw.line_mode();
-        //         draw.polygon()
-        //             .events(slice.into_iter())
-        //             .color(self.foreground_color);
-        //         draw.polygon()
-        //             .events(mirrored_slice.clone().into_iter())
-        //             .color(self.foreground_color);
-        //         // draw.polyline().color(LIMEGREEN).events(slice.into_iter());
-        //         // draw.polyline().color(LIMEGREEN).events(mirrored_slice.clone().into_iter());
-        //     })
-        //     .collect::<Vec<_>>();
-
-        self.petals.iter().enumerate().for_each(|(i, petal)| {
-            let draw = draw.rotate(deg_to_rad(2. * self.angle * i as f32));
+        match self.mode {
+            Mode::Kaleidoscope => return self.kaleidoscope(app, draw),
+            Mode::Drawing => return self.view_drawing(draw),
+            Mode::Outline => return self.view_outlines(draw),
+            Mode::Petals => (),
+        }
 
-            petal.view(app, &draw);
-        });
+        // From the outside in, so the inner rings lie on top
+        self.shown_rings()
+            .iter()
+            .rev()
+            .for_each(|ring| ring.view(app, draw));
 
         self.centerpiece.view(app, draw);
     }
@@ -199,8 +327,8 @@ impl Nannou for Model {
         });
         // self.offsets.sort_by(f32::total_cmp)
         //
-        self.petals.iter_mut().for_each(|petal| {
-            petal.update();
+        self.rings.iter_mut().for_each(|ring| {
+            ring.update();
         });
     }
 }
@@ -225,44 +353,32 @@ impl Nannou for Centerpiece {
     fn update(&mut self) {}
 }
 
-impl Nannou for Petal {
-    fn view(&self, _app: &App, draw: &Draw) {
-        let mut builder = geom::path::Builder::new().with_svg();
-        builder.move_to(self.start.to_array().into());
-        builder.cubic_bezier_to(
-            self.ctrl1.to_array().into(),
-            self.ctrl2.to_array().into(),
-            self.end.to_array().into(),
-        );
+impl Nannou for Ring {
+    fn view(&self, app: &App, draw: &Draw) {
+        let step = 360.0 / self.petal_count as f32;
+        self.petals.iter().enumerate().for_each(|(i, petal)| {
+            let draw = draw.rotate(deg_to_rad(self.rotation + step * i as f32));
 
-        let events = builder.build();
+            petal.view(app, &draw);
+            if let Some(nested) = self.nested.get(i) {
+                nested.view(app, &draw);
+            }
+        });
+    }
 
-        draw.polygon()
-            .events(events.iter())
-            .color(self.foreground_color);
+    fn update(&mut self) {
+        self.petals.iter_mut().for_each(|petal| {
+            petal.update();
+        });
+    }
+}
 
-        // draw.polyline()
-        //     .weight(1.0)
-        //     .color(self.foreground_color)
-        //     .events(events.iter());
-        //
-        draw.scale_y(-1.0)
-            .polygon()
-            .events(events.iter())
+impl Nannou for Petal {
+    fn view(&self, _app: &App, draw: &Draw) {
+        draw.polygon()
+            .points(self.outline())
             .color(self.foreground_color);
 
-        // draw.scale_x(-1.0)
-        //     .polyline()
-        //     .weight(3.0)
-        //     .color(self.foreground_color)
-        //     .events(events.iter());
-
-        // draw.scale_x(-1.0)
-        //     .polyline()
-        //     .weight(1.0)
-        //     .color(self.background_color)
-        //     .events(events.iter());
-
         // Debug
         // draw.ellipse().radius(5.0).xy(self.start).color(GREEN);
         // draw.ellipse().radius(5.0).xy(self.ctrl1).color(RED);
@@ -274,13 +390,34 @@ impl Nannou for Petal {
 }
 
 impl Model {
-    fn bezier_curve(
-        &self,
-        start: Point2,
-        ctrl1: Point2,
-        ctrl2: Point2,
-        end: Point2,
-    ) -> nannou::lyon::path::builder::WithSvg<geom::path::Builder> {
+    /// The slice mirrored and repeated all around, `symmetry` times.
+    fn kaleidoscope(&self, app: &App, draw: &Draw) {
+        let size = app.window_rect().w().min(app.window_rect().h()) / 2.0;
+        let angle = 180. / self.symmetry as f32;
+        let slice = self.slice(pt2(0., 0.), angle, size);
+        let curves = self.curves(pt2(0., 0.), angle, size);
+
+        (0..self.symmetry).for_each(|i| {
+            let draw = draw.rotate(deg_to_rad(i as f32 * angle * 2.));
+
+            for draw in [draw.clone(), draw.scale_y(-1.0)] {
+                draw.polygon()
+                    .events(slice.iter())
+                    .color(self.foreground_color);
+                curves
+                    .iter()
+                    .zip(self.palette.iter().cycle())
+                    .for_each(|(curve, color)| {
+                        draw.polyline()
+                            .weight(3.0)
+                            .events(curve.iter())
+                            .color(*color);
+                    });
+            }
+        });
+    }
+
+    fn bezier_curve(&self, start: Point2, ctrl1: Point2, ctrl2: Point2, end: Point2) -> geom::Path {
         let mut builder = geom::path::Builder::new().with_svg();
         builder.move_to(start.to_array().into());
         builder.cubic_bezier_to(
@@ -288,10 +425,12 @@ impl Model {
             ctrl2.to_array().into(),
             end.to_array().into(),
         );
-        builder
+        builder.build()
     }
 
-    fn slice(&self, origin: Point2, angle: f32, length: f32) -> geom::Path {
+    /// Corners of the slice: A at the origin, C at `length` along the x axis, and B above C
+    /// at `angle` degrees from A.
+    fn corners(&self, origin: Point2, angle: f32, length: f32) -> (Point2, Point2, Point2) {
         //              B
         //
         //   angle (degrees)
@@ -300,6 +439,13 @@ impl Model {
         let a = origin;
         let c = pt2(origin.x + length, origin.y);
         let b = pt2(c.x, origin.y + (deg_to_rad(angle).tan() * length));
+        (a, b, c)
+    }
+
+    /// The triangle, with a zigzag through it from every offset on AB to the same offset on
+    /// AC. As the offsets move out, so does the zigzag.
+    fn slice(&self, origin: Point2, angle: f32, length: f32) -> geom::Path {
+        let (a, b, c) = self.corners(origin, angle, length);
 
         let mut points = vec![a, c, b, a];
         points.push(b.lerp(c, 0.5));
@@ -311,47 +457,27 @@ impl Model {
 
         let mut builder = geom::path::Builder::new().with_svg();
         builder.move_to(origin.to_array().into());
-
-        let ctrp1 = pt2(
-            b.x + self.offsets.iter().last().unwrap_or(&0.),
-            b.y + self.offsets[0],
-        );
-        let ctrp2 = pt2(
-            c.x + self.offsets.iter().last().unwrap_or(&0.),
-            c.y + self.offsets[0],
-        );
-
         points.iter().for_each(|point| {
             builder.line_to(point.to_array().into());
-            // builder.cubic_bezier_to(
-            //     ctrp1.to_array().into(),
-            //     ctrp2.to_array().into(),
-            //     point.to_array().into(),
-            // );
         });
 
         builder.build()
     }
-}
 
-impl Petal {
-    pub fn generate(amount: usize) -> Vec<Petal> {
-        let angle = 360. / amount as f32;
-        let length = 200.;
-
-        let start = pt2(0., 0.);
-        let end = pt2(length, 0.);
-        let height = length * deg_to_rad(angle).tan();
-        let ctrl1 = pt2(random_range(0., height), random_range(0., height));
-        let ctrl2 = pt2(random_range(0., height), random_range(0., height));
-
-        (0..amount)
-            .map(|_| Petal {
-                start,
-                ctrl1,
-                ctrl2,
-                end,
-                ..Petal::default()
+    /// A curve across the slice for every offset, bulging out more the further out it is.
+    fn curves(&self, origin: Point2, angle: f32, length: f32) -> Vec<geom::Path> {
+        let (a, b, c) = self.corners(origin, angle, length);
+
+        self.offsets
+            .iter()
+            .map(|offset| {
+                let bulge = (offset * 1.3).min(1.0);
+                self.bezier_curve(
+                    a.lerp(b, *offset),
+                    a.lerp(b, bulge),
+                    a.lerp(c, bulge),
+                    a.lerp(c, *offset),
+                )
             })
             .collect()
     }
@@ -1,14 +1,37 @@
+use std::hash::{Hash, Hasher};
+
+use bertools::schemes;
+use bertools::Record;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::seq::SliceRandom;
+use nannou::rand::{Rng, SeedableRng};
 use nannou::{color::Hsla, geom::Point2};
 
+use crate::drawing::Drawing;
+use crate::morph::Morph;
+use crate::petals::{PetalKind, Variation};
+
 pub struct Model {
+    pub seed: String,
     pub background_color: Hsla,
     pub foreground_color: Hsla,
-    pub angle: f32,
+    pub palette: Vec<Hsla>,
+    pub mode: Mode,
+    /// How many times the slice of the kaleidoscope goes around, each time mirrored.
+    pub symmetry: usize,
     pub offsets: Vec<f32>,
     pub centerpiece: Centerpiece,
-    pub petals: Vec<Petal>,
+    /// From the inside out.
+    pub rings: Vec<Ring>,
+    /// Morphs the rings into those of other mandalas and back, instead of showing `rings`.
+    pub morph: Option<Morph>,
+    /// The lines of `Mode::Outline`, kept until the rings or the symmetry change.
+    pub lines: Option<Vec<Vec<Point2>>>,
+    pub drawing: Drawing,
+    pub recorder: Option<Record>,
 }
 
+#[derive(Clone)]
 pub struct Petal {
     pub background_color: Hsla,
     pub foreground_color: Hsla,
@@ -16,9 +39,225 @@ pub struct Petal {
     pub ctrl1: Point2,
     pub ctrl2: Point2,
     pub end: Point2,
+    /// The upper half of the outline, from the start to the tip, see `PetalKind::half`.
+    pub half: Vec<Point2>,
 }
 pub struct Centerpiece {
     pub radius: f32,
     pub background_color: Hsla,
     pub foreground_color: Hsla,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Rings of petals around the centerpiece.
+    Petals,
+    /// The lines of the petals and the centerpiece, to colour in.
+    Outline,
+    /// A slice that changes with the offsets, mirrored all around.
+    Kaleidoscope,
+    /// Strokes drawn with the mouse, repeated all around.
+    Drawing,
+}
+
+impl Mode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "petals" => Some(Mode::Petals),
+            "outline" => Some(Mode::Outline),
+            "kaleidoscope" => Some(Mode::Kaleidoscope),
+            "drawing" => Some(Mode::Drawing),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Mode::Petals => Mode::Outline,
+            Mode::Outline => Mode::Kaleidoscope,
+            Mode::Kaleidoscope => Mode::Drawing,
+            Mode::Drawing => Mode::Petals,
+        }
+    }
+}
+
+/// A ring of petals around the centre, all of the same shape, give or take its variation.
+#[derive(Clone)]
+pub struct Ring {
+    pub petal_count: usize,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub shape: PetalShape,
+    pub color: Hsla,
+    /// Of the first petal, in degrees.
+    pub rotation: f32,
+    pub variation: Variation,
+    pub petals: Vec<Petal>,
+    /// Inside the petals, one for each, when the variation has them.
+    pub nested: Vec<Petal>,
+}
+
+/// Control points of the curve of a petal, relative to the room it has. X goes from the
+/// start of the petal (0) to its tip (1), y from the middle of the petal (0) to the edge of
+/// its slice of the ring (1). Beyond 1 it overlaps with the petals next to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PetalShape {
+    pub kind: PetalKind,
+    pub ctrl1: Point2,
+    pub ctrl2: Point2,
+}
+
+/// Petal counts that make a pleasing ring.
+const PETAL_COUNTS: [usize; 8] = [6, 8, 10, 12, 16, 18, 24, 36];
+/// Rings start just outside the centerpiece, and end just inside the window.
+const INNER_RADIUS: f32 = 50.0;
+const OUTER_RADIUS: f32 = 360.0;
+
+/// The colours to pick the rings from, by name.
+pub fn palette(name: &str) -> Option<Vec<Hsla>> {
+    match name {
+        "valentine" => Some(schemes::SCHEME_VALENTINE.to_vec()),
+        "flatgreen" => Some(schemes::SCHEME_FLATGREEN.to_vec()),
+        "navy" => Some(schemes::navy().to_vec()),
+        _ => None,
+    }
+}
+
+impl Model {
+    /// A mandala with the rings in `rings`: a number of rings from the seed, or a list of
+    /// rings separated by commas, see `Ring::parse`. Empty for any number of rings.
+    pub fn new(seed: String, palette: Vec<Hsla>, rings: &str) -> Self {
+        let default = Self::default();
+
+        // Convert seed String to a u64
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        seed.hash(&mut hasher);
+        let seed_n = hasher.finish();
+        let mut rng = StdRng::seed_from_u64(seed_n);
+
+        let mut rings = match rings.trim().parse::<usize>() {
+            Ok(count) => Ring::generate_all(count, &mut rng, &palette),
+            Err(_) => rings
+                .split(',')
+                .filter(|spec| !spec.trim().is_empty())
+                .filter_map(|spec| {
+                    let ring = Ring::parse(spec, &mut rng, &palette);
+                    if ring.is_none() {
+                        println!("Can't read the ring {:?}", spec);
+                    }
+                    ring
+                })
+                .collect(),
+        };
+        if rings.is_empty() {
+            let count = rng.gen_range(3..=6);
+            rings = Ring::generate_all(count, &mut rng, &palette);
+        }
+        rings.sort_by(|a, b| a.inner_radius.total_cmp(&b.inner_radius));
+
+        Self {
+            seed,
+            palette,
+            rings,
+            ..default
+        }
+    }
+
+    /// The rings as they are shown, part of the way to the next mandala while morphing.
+    pub fn shown_rings(&self) -> Vec<Ring> {
+        match &self.morph {
+            Some(morph) => morph.rings(),
+            None => self.rings.clone(),
+        }
+    }
+}
+
+impl Ring {
+    /// A ring of `petal_count` petals from `inner_radius` to `outer_radius`, in a shape and
+    /// colour from the seed.
+    pub fn generate(
+        rng: &mut StdRng,
+        palette: &[Hsla],
+        petal_count: usize,
+        inner_radius: f32,
+        outer_radius: f32,
+    ) -> Self {
+        let shape = PetalShape {
+            kind: PetalKind::generate(rng),
+            ctrl1: Point2::new(rng.gen_range(0.0..0.6), rng.gen_range(0.2..1.5)),
+            ctrl2: Point2::new(rng.gen_range(0.4..1.0), rng.gen_range(0.2..1.5)),
+        };
+        // Either in line with the ring inside it, or half a petal on
+        let rotation = match rng.gen_bool(0.5) {
+            true => 0.0,
+            false => 180.0 / petal_count as f32,
+        };
+        let mut ring = Self {
+            petal_count,
+            inner_radius,
+            outer_radius,
+            shape,
+            color: *palette.choose(rng).unwrap(),
+            rotation,
+            variation: Variation::generate(rng),
+            petals: vec![],
+            nested: vec![],
+        };
+        ring.petals = Petal::generate(&ring);
+        ring.nested = Petal::nested(&ring);
+        ring
+    }
+
+    /// `count` rings between the centerpiece and the edge, each a bit into the one inside it.
+    pub fn generate_all(count: usize, rng: &mut StdRng, palette: &[Hsla]) -> Vec<Self> {
+        let weights: Vec<f32> = (0..count).map(|_| rng.gen_range(0.5..1.5)).collect();
+        let total: f32 = weights.iter().sum();
+
+        let mut inner = INNER_RADIUS;
+        weights
+            .iter()
+            .map(|weight| {
+                let outer = inner + weight / total * (OUTER_RADIUS - INNER_RADIUS);
+                let petal_count = *PETAL_COUNTS.choose(rng).unwrap();
+                let ring = Ring::generate(rng, palette, petal_count, inner * 0.85, outer);
+                inner = outer;
+                ring
+            })
+            .collect()
+    }
+
+    /// Rings like `12@120-220`: 12 petals from 120 to 220 pixels from the centre. Turn the
+    /// ring with `/` and a number of degrees, like `12@120-220/15`, and pick the shape of
+    /// its petals with `:` and a name from `PetalKind::parse`, like `12@120-220/15:lobed`.
+    /// The rest of the shape and the colour come from the seed.
+    pub fn parse(spec: &str, rng: &mut StdRng, palette: &[Hsla]) -> Option<Self> {
+        let (spec, kind) = match spec.split_once(':') {
+            Some((spec, kind)) => (spec, Some(PetalKind::parse(kind.trim())?)),
+            None => (spec, None),
+        };
+        let (petal_count, rest) = spec.split_once('@')?;
+        let (radii, rotation) = match rest.split_once('/') {
+            Some((radii, rotation)) => (radii, Some(rotation.trim().parse().ok()?)),
+            None => (rest, None),
+        };
+        let (inner, outer) = radii.split_once('-')?;
+
+        let petal_count: usize = petal_count.trim().parse().ok()?;
+        let inner: f32 = inner.trim().parse().ok()?;
+        let outer: f32 = outer.trim().parse().ok()?;
+        if petal_count < 3 || inner >= outer {
+            return None;
+        }
+
+        let mut ring = Ring::generate(rng, palette, petal_count, inner, outer);
+        if let Some(rotation) = rotation {
+            ring.rotation = rotation;
+        }
+        if let Some(kind) = kind {
+            ring.shape.kind = kind;
+            ring.petals = Petal::generate(&ring);
+            ring.nested = Petal::nested(&ring);
+        }
+        Some(ring)
+    }
+}
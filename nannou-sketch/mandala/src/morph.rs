@@ -0,0 +1,145 @@
+use nannou::color::Mix;
+
+use crate::models::{Model, Petal, PetalShape, Ring};
+
+/// An animation from one mandala to the next and back to the first, that loops without a
+/// seam: the frame after the last one is the first one again.
+pub struct Morph {
+    /// The rings of every mandala, in the order they are shown. All have the same number of
+    /// rings and of petals in each ring.
+    pub keyframes: Vec<Vec<Ring>>,
+    /// For the whole loop, every mandala gets an equal share.
+    pub frames: usize,
+    pub frame: usize,
+}
+
+impl Morph {
+    /// From the mandala of `model` through those of `seeds`, in `frames` frames. The other
+    /// mandalas get the rings of `rings` like `Model::new`, or as many rings as `model` when
+    /// that doesn't list any. Their petal counts follow `model`, so every petal has one to
+    /// morph into.
+    pub fn new(model: &Model, seeds: &[String], rings: &str, frames: usize) -> Self {
+        let layout = match rings.contains('@') {
+            true => rings.to_string(),
+            false => model.rings.len().to_string(),
+        };
+
+        let mut keyframes = vec![model.rings.clone()];
+        for seed in seeds {
+            let mut rings = Model::new(seed.clone(), model.palette.clone(), &layout).rings;
+            rings
+                .iter_mut()
+                .zip(&model.rings)
+                .for_each(|(ring, like)| ring.conform(like));
+            keyframes.push(rings);
+        }
+
+        Self {
+            keyframes,
+            frames: frames.max(1),
+            frame: 0,
+        }
+    }
+
+    /// The rings at the current frame.
+    pub fn rings(&self) -> Vec<Ring> {
+        let count = self.keyframes.len();
+        let position = (self.frame % self.frames) as f32 / self.frames as f32 * count as f32;
+        let from = position.floor() as usize % count;
+        let to = (from + 1) % count;
+        let factor = ease(position.fract());
+
+        self.keyframes[from]
+            .iter()
+            .zip(&self.keyframes[to])
+            .map(|(a, b)| a.lerp(b, factor))
+            .collect()
+    }
+}
+
+/// Slow at the start and at the end, so every mandala rests a moment before the next.
+fn ease(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl Ring {
+    /// The same number of petals as `like`.
+    pub fn conform(&mut self, like: &Ring) {
+        if self.petal_count == like.petal_count {
+            return;
+        }
+        if self.rotation != 0.0 {
+            self.rotation = 180.0 / like.petal_count as f32;
+        }
+        self.petal_count = like.petal_count;
+        self.petals = Petal::generate(self);
+        self.nested = Petal::nested(self);
+    }
+
+    /// Part of the way to `other`, from 0 (this ring) to 1 (the other one). The kind of
+    /// petal and the variation switch halfway, the petals themselves change smoothly.
+    /// Nested petals grow out of nothing or shrink away when only one ring has them.
+    pub fn lerp(&self, other: &Ring, factor: f32) -> Ring {
+        let lerp = |a: f32, b: f32| a + (b - a) * factor;
+        let nearest = match factor < 0.5 {
+            true => self,
+            false => other,
+        };
+        // A petal shrunk to nothing at its start, to grow from or shrink to
+        let vanished = |petal: &Petal| Petal {
+            end: petal.start,
+            ctrl1: petal.start,
+            ctrl2: petal.start,
+            half: vec![petal.start; petal.half.len()],
+            ..petal.clone()
+        };
+        let (from, to): (Vec<Petal>, Vec<Petal>) = match (&self.nested, &other.nested) {
+            (from, to) if to.is_empty() => (from.clone(), from.iter().map(vanished).collect()),
+            (from, to) if from.is_empty() => (to.iter().map(vanished).collect(), to.clone()),
+            (from, to) => (from.clone(), to.clone()),
+        };
+        Ring {
+            petal_count: self.petal_count,
+            inner_radius: lerp(self.inner_radius, other.inner_radius),
+            outer_radius: lerp(self.outer_radius, other.outer_radius),
+            shape: PetalShape {
+                ctrl1: self.shape.ctrl1.lerp(other.shape.ctrl1, factor),
+                ctrl2: self.shape.ctrl2.lerp(other.shape.ctrl2, factor),
+                kind: nearest.shape.kind,
+            },
+            color: self.color.mix(&other.color, factor),
+            rotation: lerp(self.rotation, other.rotation),
+            variation: nearest.variation,
+            petals: self
+                .petals
+                .iter()
+                .zip(&other.petals)
+                .map(|(a, b)| a.lerp(b, factor))
+                .collect(),
+            nested: from
+                .iter()
+                .zip(&to)
+                .map(|(a, b)| a.lerp(b, factor))
+                .collect(),
+        }
+    }
+}
+
+impl Petal {
+    pub fn lerp(&self, other: &Petal, factor: f32) -> Petal {
+        Petal {
+            background_color: self.background_color.mix(&other.background_color, factor),
+            foreground_color: self.foreground_color.mix(&other.foreground_color, factor),
+            start: self.start.lerp(other.start, factor),
+            ctrl1: self.ctrl1.lerp(other.ctrl1, factor),
+            ctrl2: self.ctrl2.lerp(other.ctrl2, factor),
+            end: self.end.lerp(other.end, factor),
+            half: self
+                .half
+                .iter()
+                .zip(&other.half)
+                .map(|(a, b)| a.lerp(*b, factor))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::f32::consts::PI;
+
+use nannou::color::Hsla;
+use nannou::geom::Point2;
+use nannou::noise::{self, NoiseFn, Seedable};
+use nannou::prelude::*;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::Rng;
+
+use crate::models::{Petal, Ring};
+
+/// Steps along half of a petal, from its start to its tip. The same for every shape, so
+/// petals of different shapes morph into each other point by point.
+pub const HALF_STEPS: usize = 48;
+
+/// The shapes a petal can have. Each is a curve from the start of the petal to its tip,
+/// mirrored for the other half.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PetalKind {
+    /// One round lobe.
+    Teardrop,
+    /// Sharp at both ends.
+    Pointed,
+    /// A teardrop with this many bumps along its edge.
+    Lobed(usize),
+    /// A teardrop with this many teeth along its edge, pointing to the tip.
+    Serrated(usize),
+    /// Two lobes, one after the other.
+    DoubleBezier,
+    /// Gielis' superformula, with m, n1, n2 and n3.
+    Superformula(f32, f32, f32, f32),
+}
+
+impl PetalKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "teardrop" => Some(PetalKind::Teardrop),
+            "pointed" => Some(PetalKind::Pointed),
+            "lobed" => Some(PetalKind::Lobed(3)),
+            "serrated" => Some(PetalKind::Serrated(7)),
+            "double" | "double-bezier" => Some(PetalKind::DoubleBezier),
+            "superformula" => Some(PetalKind::Superformula(6.0, 1.0, 1.0, 1.0)),
+            _ => None,
+        }
+    }
+
+    /// Any of the shapes, teardrops a bit more often than the others.
+    pub fn generate(rng: &mut StdRng) -> Self {
+        match rng.gen_range(0..8) {
+            0 => PetalKind::Pointed,
+            1 => PetalKind::Lobed(rng.gen_range(2..=5)),
+            2 => PetalKind::Serrated(rng.gen_range(4..=10)),
+            3 => PetalKind::DoubleBezier,
+            4 => PetalKind::Superformula(
+                rng.gen_range(2..=8) as f32,
+                rng.gen_range(0.5..4.0),
+                rng.gen_range(0.5..3.0),
+                rng.gen_range(0.5..3.0),
+            ),
+            _ => PetalKind::Teardrop,
+        }
+    }
+
+    /// The upper half of a petal lying on the x axis, from `start` to `end`, bulging out
+    /// towards `ctrl1` and `ctrl2`.
+    pub fn half(&self, start: Point2, ctrl1: Point2, ctrl2: Point2, end: Point2) -> Vec<Point2> {
+        let curve = |t: f32| bezier(start, ctrl1, ctrl2, end, t);
+        let widen = |t: f32, factor: f32| {
+            let point = curve(t);
+            pt2(point.x, point.y * factor)
+        };
+        let width = ctrl1.y.max(ctrl2.y);
+        // The superformula at its widest, to scale it to the width
+        let largest = match *self {
+            PetalKind::Superformula(m, n1, n2, n3) => (0..=HALF_STEPS)
+                .map(|i| superformula(PI * i as f32 / HALF_STEPS as f32, m, n1, n2, n3))
+                .fold(f32::EPSILON, f32::max),
+            _ => 1.0,
+        };
+
+        (0..=HALF_STEPS)
+            .map(|i| {
+                let t = i as f32 / HALF_STEPS as f32;
+                match *self {
+                    PetalKind::Teardrop => curve(t),
+                    PetalKind::Pointed => bezier(start, ctrl1, ctrl1, end, t),
+                    PetalKind::Lobed(lobes) => {
+                        widen(t, 1.0 + 0.25 * (t * lobes as f32 * PI).sin().abs())
+                    }
+                    PetalKind::Serrated(teeth) => widen(t, 1.0 + 0.2 * (t * teeth as f32).fract()),
+                    PetalKind::DoubleBezier => {
+                        // The first lobe ends at a narrow waist halfway, the second one is
+                        // a bit smaller
+                        let waist = pt2(start.lerp(end, 0.5).x, width * 0.2);
+                        let squeeze = |ctrl: Point2, from: Point2, size: f32| {
+                            pt2(from.x + (ctrl.x - start.x) * 0.5, ctrl.y * size)
+                        };
+                        if t < 0.5 {
+                            let (c1, c2) = (squeeze(ctrl1, start, 1.0), squeeze(ctrl2, start, 1.0));
+                            bezier(start, c1, c2, waist, t * 2.0)
+                        } else {
+                            let (c1, c2) = (squeeze(ctrl1, waist, 0.8), squeeze(ctrl2, waist, 0.8));
+                            bezier(waist, c1, c2, end, t * 2.0 - 1.0)
+                        }
+                    }
+                    PetalKind::Superformula(m, n1, n2, n3) => {
+                        let radius = |angle: f32| superformula(angle, m, n1, n2, n3);
+                        // Around the middle of the petal, from the start to the tip
+                        let angle = PI * (1.0 - t);
+                        let middle = start.lerp(end, 0.5);
+                        let length = (end.x - start.x) / 2.0;
+                        pt2(
+                            middle.x - angle.cos() * length,
+                            radius(angle) / largest * angle.sin() * width,
+                        )
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// A point on a cubic Bézier curve, `t` from 0 at the start to 1 at the end.
+pub fn bezier(start: Point2, ctrl1: Point2, ctrl2: Point2, end: Point2, t: f32) -> Point2 {
+    let u = 1.0 - t;
+    start * u * u * u + ctrl1 * 3.0 * u * u * t + ctrl2 * 3.0 * u * t * t + end * t * t * t
+}
+
+/// Radius of the superformula at `angle`, see https://en.wikipedia.org/wiki/Superformula
+fn superformula(angle: f32, m: f32, n1: f32, n2: f32, n3: f32) -> f32 {
+    let a = (m * angle / 4.0).cos().abs().powf(n2);
+    let b = (m * angle / 4.0).sin().abs().powf(n3);
+    (a + b).max(f32::EPSILON).powf(-1.0 / n1)
+}
+
+/// How the petals of a ring differ from each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variation {
+    /// Length of every other petal, relative to the others. 1 for all the same.
+    pub alternate: f32,
+    /// How far the edges wobble, relative to the width of the petal. 0 for smooth edges.
+    pub wobble: f32,
+    pub noise_seed: u32,
+    /// Size of a smaller petal inside every petal, relative to it. 0 for none.
+    pub nested: f32,
+}
+
+impl Default for Variation {
+    fn default() -> Self {
+        Self {
+            alternate: 1.0,
+            wobble: 0.0,
+            noise_seed: 0,
+            nested: 0.0,
+        }
+    }
+}
+
+impl Variation {
+    /// Now and then alternating sizes, wobbly edges or nested petals.
+    pub fn generate(rng: &mut StdRng) -> Self {
+        Self {
+            alternate: match rng.gen_bool(0.3) {
+                true => rng.gen_range(0.6..0.85),
+                false => 1.0,
+            },
+            wobble: match rng.gen_bool(0.3) {
+                true => rng.gen_range(0.05..0.15),
+                false => 0.0,
+            },
+            noise_seed: rng.gen(),
+            nested: match rng.gen_bool(0.4) {
+                true => rng.gen_range(0.4..0.7),
+                false => 0.0,
+            },
+        }
+    }
+}
+
+impl Petal {
+    /// The upper half of the outline and its mirror image, all the way around.
+    pub fn outline(&self) -> Vec<Point2> {
+        let back = self.half.iter().rev().map(|point| pt2(point.x, -point.y));
+        self.half.iter().copied().chain(back).collect()
+    }
+
+    /// The petals of `ring`, all pointing right. The ring turns them into place.
+    pub fn generate(ring: &Ring) -> Vec<Petal> {
+        let length = ring.outer_radius - ring.inner_radius;
+        // Half the width of a slice of the ring, at a distance from the centre
+        let room = |distance: f32| distance * deg_to_rad(180. / ring.petal_count as f32).tan();
+        let variation = ring.variation;
+        let noise = noise::OpenSimplex::new().set_seed(variation.noise_seed);
+
+        (0..ring.petal_count)
+            .map(|i| {
+                // Only with an even number of petals, or two short ones would meet
+                let size = match (i % 2, ring.petal_count % 2) {
+                    (1, 0) => variation.alternate,
+                    _ => 1.0,
+                };
+                let place = |relative: Point2| {
+                    let distance = ring.inner_radius + relative.x * length * size;
+                    pt2(distance, relative.y * room(distance))
+                };
+
+                let start = pt2(ring.inner_radius, 0.);
+                let end = pt2(ring.inner_radius + length * size, 0.);
+                let ctrl1 = place(ring.shape.ctrl1);
+                let ctrl2 = place(ring.shape.ctrl2);
+
+                let width = ctrl1.y.max(ctrl2.y);
+                let half = ring
+                    .shape
+                    .kind
+                    .half(start, ctrl1, ctrl2, end)
+                    .into_iter()
+                    .enumerate()
+                    .map(|(step, point)| {
+                        // Only where the petal is wide, so the ends stay put
+                        let t = step as f64 / HALF_STEPS as f64;
+                        let shift = noise.get([i as f64, t * 3.0]) as f32;
+                        let envelope = (t as f32 * PI).sin();
+                        pt2(
+                            point.x,
+                            point.y + shift * envelope * variation.wobble * width,
+                        )
+                    })
+                    .collect();
+
+                Petal {
+                    start,
+                    ctrl1,
+                    ctrl2,
+                    end,
+                    half,
+                    foreground_color: Hsla {
+                        alpha: 0.6,
+                        ..ring.color
+                    },
+                    ..Petal::default()
+                }
+            })
+            .collect()
+    }
+
+    /// A smaller, lighter petal inside every petal of `ring`, when it has any.
+    pub fn nested(ring: &Ring) -> Vec<Petal> {
+        let scale = ring.variation.nested;
+        if scale <= 0.0 {
+            return vec![];
+        }
+        let mut color = ring.color;
+        color.lightness = (color.lightness + 0.25).min(0.95);
+        color.alpha = 0.8;
+        let shrink = |start: Point2, point: Point2| start + (point - start) * scale;
+
+        ring.petals
+            .iter()
+            .map(|petal| Petal {
+                start: petal.start,
+                ctrl1: shrink(petal.start, petal.ctrl1),
+                ctrl2: shrink(petal.start, petal.ctrl2),
+                end: shrink(petal.start, petal.end),
+                half: petal
+                    .half
+                    .iter()
+                    .map(|point| shrink(petal.start, *point))
+                    .collect(),
+                foreground_color: color,
+                ..Petal::default()
+            })
+            .collect()
+    }
+}
@@ -1,4 +1,9 @@
-use bertools::do_save;
+use std::env;
+
+use bertools::grid::Grid;
+use bertools::tween::{nearest, Transition, Tween};
+use bertools::wfc::{Placed, Tileset, Wfc};
+use bertools::{do_save, Record};
 use nannou::prelude::*;
 
 const ASSETS: &str = "truchet_bold";
@@ -14,6 +19,17 @@ struct Model {
 
     tiles: Vec<Tile>,
     currently_hovered_tile: Option<usize>,
+
+    /// Which textures fit next to each other, when there is a tileset for them.
+    tileset: Option<Tileset>,
+    seed: String,
+    columns: usize,
+    rows: usize,
+
+    /// Frames since the start, to time the turning tiles by.
+    frame: u64,
+    transition: Transition,
+    recorder: Option<Record>,
 }
 
 #[derive(Debug)]
@@ -23,15 +39,36 @@ struct Tile {
 
     rotation: f32,
     texture_index: usize,
+    /// In the grid, a row at a time from the top left.
+    cell: usize,
+    /// Turned by hand, kept when the others are fitted around it.
+    pinned: bool,
+    /// On its way to `rotation`.
+    turning: Option<Tween>,
 }
 
 impl Tile {
     fn draw(&self, draw: &Draw, model: &Model) {
         draw.texture(&model.textures.get(self.texture_index).unwrap())
             .xy(self.position)
-            .rotate(self.rotation)
+            .rotate(self.angle(model.frame))
             .w_h(TILE_SIZE, TILE_SIZE);
     }
+
+    /// How far it is turned at `frame`.
+    fn angle(&self, frame: u64) -> f32 {
+        match self.turning {
+            Some(tween) => tween.value(frame),
+            None => self.rotation,
+        }
+    }
+
+    /// Turn to `rotation` bit by bit, from where it is at `frame`, starting at `start`.
+    fn turn_to(&mut self, rotation: f32, transition: &Transition, frame: u64, start: u64) {
+        let from = self.angle(frame);
+        self.turning = Some(transition.tween(from, rotation, start));
+        self.rotation = rotation;
+    }
 }
 
 fn model(app: &App) -> Model {
@@ -45,19 +82,45 @@ fn model(app: &App) -> Model {
         .build()
         .unwrap();
 
-    // Load the images from the assets directory
-    let dir = app.assets_path().unwrap().join(ASSETS);
-    let textures = std::fs::read_dir(dir)
-        .unwrap()
-        .filter_map(|entry| {
-            let entry = entry.unwrap();
-            let path = entry.path();
-            if path.is_file() && !path.starts_with(".") {
-                Some(path)
-            } else {
+    // Fit the images of a tileset together, like `rainbow_large` for
+    // assets/rainbow_large.tiles. Without one, all images in ASSETS at random.
+    let name = env::var("TILESET").unwrap_or_else(|_| ASSETS.to_string());
+    let assets = app.assets_path().unwrap();
+    let tileset_path = assets.join(format!("{}.tiles", name));
+    let tileset = match tileset_path.exists() {
+        true => match Tileset::open(&tileset_path) {
+            Ok(tileset) => Some(tileset),
+            Err(e) => {
+                println!("Error reading {:?}: {:?}", tileset_path, e);
                 None
             }
-        })
+        },
+        false => None,
+    };
+    let seed = env::var("SEED").unwrap_or_else(|_| random::<u32>().to_string());
+
+    // Load the images from the assets directory
+    let paths = match &tileset {
+        Some(tileset) => tileset
+            .tiles
+            .iter()
+            .map(|tile| assets.join(&tile.name))
+            .collect::<Vec<_>>(),
+        None => std::fs::read_dir(assets.join(&name))
+            .unwrap()
+            .filter_map(|entry| {
+                let entry = entry.unwrap();
+                let path = entry.path();
+                if path.is_file() && !path.starts_with(".") {
+                    Some(path)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>(),
+    };
+    let textures = paths
+        .into_iter()
         .map(|path| {
             dbg!(&path);
             wgpu::Texture::from_path(app, path).unwrap()
@@ -69,10 +132,13 @@ fn model(app: &App) -> Model {
     let tiles_y = (app.window_rect().h() / TILE_SIZE) as i32 + 2;
 
     let mut tiles = Vec::new();
+    let (columns, rows) = ((tiles_x / 2 * 2) as usize, (tiles_y / 2 * 2) as usize);
 
     for x in (-tiles_x / 2)..(tiles_x / 2) {
         for y in (-tiles_y / 2)..(tiles_y / 2) {
             let id = (x * 10 + y) as usize;
+            // Rows go down from the top
+            let cell = (tiles_y / 2 - 1 - y) as usize * columns + (x + tiles_x / 2) as usize;
 
             let x = x as f32 * TILE_SIZE;
             let y = y as f32 * TILE_SIZE;
@@ -85,18 +151,78 @@ fn model(app: &App) -> Model {
                 texture_index,
                 position,
                 rotation,
+                cell,
+                pinned: false,
+                turning: None,
             });
         }
     }
 
-    Model {
+    // How the tiles turn, press O and P for another easing and spread. The same seed spreads
+    // the turns of R the same way, even without one
+    let transition = Transition::from_env(&env::var("SEED").unwrap_or_default());
+    let recorder = match env::var("RECORD") {
+        Ok(_) => Some(Record::new(app)),
+        Err(_) => None,
+    };
+
+    let mut model = Model {
         textures,
         tiles,
         currently_hovered_tile: None,
+        tileset,
+        seed,
+        columns,
+        rows,
+        frame: 0,
+        transition,
+        recorder,
+    };
+    collapse(&mut model);
+    model
+}
+
+/// Pick and turn the textures so their edges meet, with wave function collapse, when
+/// there is a tileset. Tiles turned by hand stay as they are.
+fn collapse(model: &mut Model) {
+    let Some(tileset) = &model.tileset else {
+        return;
+    };
+    println!("Seed {}", model.seed);
+    let grid = Grid::new(model.columns, model.rows, TILE_SIZE);
+    let mut wfc = Wfc::new(&grid, tileset, &model.seed);
+    for tile in model.tiles.iter().filter(|tile| tile.pinned) {
+        let rotation = (tile.rotation / (PI / 2.0)).round().rem_euclid(4.0) as u8;
+        wfc.pin(
+            tile.cell,
+            Placed {
+                tile: tile.texture_index,
+                rotation,
+            },
+        );
+    }
+
+    match wfc.solve() {
+        Some(placed) => {
+            for tile in &mut model.tiles {
+                tile.texture_index = placed[tile.cell].tile;
+                tile.rotation = placed[tile.cell].rotation as f32 * PI / 2.0;
+                tile.turning = None;
+            }
+        }
+        None => println!("Can't make the tiles fit, leaving them as they are"),
     }
 }
 
-fn update(_app: &App, _model: &mut Model, _update: Update) {}
+fn update(_app: &App, model: &mut Model, _update: Update) {
+    model.frame += 1;
+    let frame = model.frame;
+    for tile in &mut model.tiles {
+        if tile.turning.is_some_and(|tween| tween.done(frame)) {
+            tile.turning = None;
+        }
+    }
+}
 
 fn view(app: &App, model: &Model, frame: Frame) {
     // Clear the frame with a black background
@@ -112,6 +238,10 @@ fn view(app: &App, model: &Model, frame: Frame) {
 
     // Write the drawing to the frame
     draw.to_frame(app, &frame).unwrap();
+
+    if let Some(recorder) = &model.recorder {
+        recorder.record(app);
+    }
 }
 
 fn mouse_released(app: &App, model: &mut Model, button: MouseButton) {
@@ -126,11 +256,13 @@ fn mouse_released(app: &App, model: &mut Model, button: MouseButton) {
     });
 
     if let Some(t) = tile {
+        let frame = model.frame;
         match button {
-            MouseButton::Left => t.rotation += PI / 2.0,
-            MouseButton::Right => t.rotation -= PI / 2.0,
+            MouseButton::Left => t.turn_to(t.rotation + PI / 2.0, &model.transition, frame, frame),
+            MouseButton::Right => t.turn_to(t.rotation - PI / 2.0, &model.transition, frame, frame),
             _ => (),
         }
+        t.pinned = true;
     }
 }
 
@@ -149,7 +281,8 @@ fn mouse_moved(_app: &App, model: &mut Model, pos: Point2) {
         // If we're now hovering over a tile, we're entering it
         if let Some(index) = current_tile_index {
             // Perform rotation only on entry
-            model.tiles[index].rotation += PI / 2.0;
+            let (frame, tile) = (model.frame, &mut model.tiles[index]);
+            tile.turn_to(tile.rotation + PI / 2.0, &model.transition, frame, frame);
         }
 
         // Update the currently hovered tile
@@ -159,14 +292,44 @@ fn mouse_moved(_app: &App, model: &mut Model, pos: Point2) {
 
 fn key_pressed(app: &App, model: &mut Model, key: Key) {
     match key {
+        // Turn the tiles, spreading from the mouse
         Key::R => {
+            let (frame, origin, area) = (model.frame, app.mouse.position(), app.window_rect());
+            // The same seed turns the same tiles the same way, press after press
+            model.transition.changes += 1;
             for tile in &mut model.tiles {
-                tile.rotation = random_range(0, 4) as f32 * PI / 2.0;
+                let from = tile.angle(frame);
+                let quarters = model.transition.pick(tile.id, 4);
+                let rotation = nearest(from, quarters as f32 * PI / 2.0, TAU);
+                let delay = model.transition.delay(tile.position, origin, area);
+                tile.turn_to(rotation, &model.transition, frame, frame + delay);
+            }
+        }
+        Key::O => {
+            model.transition.easing = model.transition.easing.next();
+            println!("Easing {:?}", model.transition.easing);
+        }
+        Key::P => {
+            model.transition.spread = model.transition.spread.next();
+            println!("Spread {:?}", model.transition.spread);
+        }
+        Key::Escape => {
+            if let Some(recorder) = &model.recorder {
+                recorder.finish();
             }
         }
         Key::S => {
             do_save(app);
         }
+        // Fit the tiles together again, around the ones turned by hand
+        Key::W => {
+            model.seed = random::<u32>().to_string();
+            collapse(model);
+        }
+        // Forget which tiles were turned by hand
+        Key::U => {
+            model.tiles.iter_mut().for_each(|t| t.pinned = false);
+        }
         Key::Key0 => {
             for tile in &mut model.tiles {
                 tile.texture_index = random_range(0, model.textures.len());